@@ -0,0 +1,435 @@
+// In-process extraction of font bundles (.zip, .tar, .tar.gz, .tar.xz) and
+// splitting of font collections. Only font entries, and optionally
+// license/readme files, are written out. Every archive directory maps to its
+// own numbered directory under a private temp dir, so that entries with the
+// same name or hostile paths cannot collide or escape, while Type 1 fonts
+// still find the metric files shipped next to them.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use flate2::read::GzDecoder;
+
+use crate::kind::{has_font_extension, is_metrics};
+use crate::sfnt;
+use crate::woff::MAX_SFNT_SIZE;
+
+#[derive(Debug, Clone, Copy)]
+enum Format { Zip, Tar, TarGz, TarXz }
+
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> io::Result<TempDir> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = env::temp_dir().join(format!("fontize-{}-{n}", std::process::id()));
+            match fs::create_dir(&path) {
+                Ok(()) => {
+                    fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+                    return Ok(TempDir(path));
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+pub struct Extracted {
+    /// Archive file name without its extension, e.g. `FiraCode` for `FiraCode.tar.xz`.
+    pub name: String,
+    /// Extracted font files paired with `<archive>/<entry>` for reporting.
+    pub fonts: Vec<(PathBuf, PathBuf)>,
+    pub docs: Vec<PathBuf>,
+    dir: TempDir,
+    dirs: HashMap<PathBuf, PathBuf>,
+}
+
+impl Extracted {
+    fn write_entry(&mut self, archive: &Path, name: &Path, reader: &mut dyn Read, with_docs: bool) -> io::Result<()> {
+        // Resource forks and AppleDouble files that macOS zips carry along
+        if name.starts_with("__MACOSX") {
+            return Ok(());
+        }
+        let Some(file_name) = name.file_name() else { return Ok(()) };
+        if file_name.to_string_lossy().starts_with("._") {
+            return Ok(());
+        }
+        let is_font = has_font_extension(name);
+        let wanted = is_font || is_metrics(name) || (with_docs && is_doc(name));
+        if !wanted {
+            return Ok(());
+        }
+
+        let parent = name.parent().unwrap_or(Path::new("")).to_path_buf();
+        let dir = match self.dirs.get(&parent) {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = self.dir.path().join(self.dirs.len().to_string());
+                fs::create_dir(&dir)?;
+                self.dirs.insert(parent, dir.clone());
+                dir
+            }
+        };
+        let path = dir.join(file_name);
+        let copied = io::copy(&mut reader.take(MAX_SFNT_SIZE + 1), &mut File::create(&path)?)?;
+        if copied > MAX_SFNT_SIZE {
+            fs::remove_file(&path)?;
+            let msg = format!("{} unpacks to more than {} MiB", name.display(), MAX_SFNT_SIZE >> 20);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+
+        if is_font {
+            self.fonts.push((path, archive.join(name)));
+        } else if !is_metrics(name) {
+            self.docs.push(path);
+        }
+        Ok(())
+    }
+}
+
+fn format_of(path: &Path) -> Option<Format> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    if name.ends_with(".zip") {
+        Some(Format::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Format::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+        Some(Format::TarXz)
+    } else {
+        None
+    }
+}
+
+fn sniff_format(path: &Path) -> io::Result<Option<Format>> {
+    let mut f = File::open(path)?;
+    let mut head = [0u8; 262];
+    let n = f.read(&mut head)?;
+    let head = &head[..n];
+
+    Ok(if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+        Some(Format::Zip)
+    } else if head.starts_with(&[0x1f, 0x8b]) {
+        Some(Format::TarGz)
+    } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(Format::TarXz)
+    } else if head.len() >= 262 && &head[257..262] == b"ustar" {
+        Some(Format::Tar)
+    } else {
+        None
+    })
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    format_of(path).is_some() || matches!(sniff_format(path), Ok(Some(_)))
+}
+
+fn archive_name(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let lower = name.to_lowercase();
+    for ext in [".tar.gz", ".tar.xz", ".tgz", ".txz", ".tar", ".zip"] {
+        if lower.ends_with(ext) {
+            return name[..name.len() - ext.len()].to_string();
+        }
+    }
+    name
+}
+
+fn is_doc(name: &Path) -> bool {
+    let stem = name.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    ["license", "licence", "ofl", "copying", "copyright", "readme", "fontlog"]
+        .iter()
+        .any(|prefix| stem.starts_with(prefix))
+}
+
+/// Writes every face of a collection out as its own font file.
+pub fn split_collection(path: &Path) -> io::Result<Extracted> {
+    let data = fs::read(path)?;
+    let mut out = Extracted {
+        name: archive_name(path),
+        fonts: Vec::new(),
+        docs: Vec::new(),
+        dir: TempDir::new()?,
+        dirs: HashMap::new(),
+    };
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    for (i, offset) in sfnt::collection_offsets(&data)?.into_iter().enumerate() {
+        let face = sfnt::extract_face(&data, offset)?;
+        let ext = if face.starts_with(b"OTTO") { "otf" } else { "ttf" };
+        let name = PathBuf::from(format!("{stem}-{i}.{ext}"));
+        out.write_entry(path, &name, &mut face.as_slice(), false)?;
+        if let Some((_, origin)) = out.fonts.last_mut() {
+            *origin = PathBuf::from(format!("{}#{i}", path.display()));
+        }
+    }
+    Ok(out)
+}
+
+pub fn extract(path: &Path, with_docs: bool) -> io::Result<Extracted> {
+    let format = match format_of(path) {
+        Some(format) => format,
+        None => sniff_format(path)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Unknown archive format (not zip/tar)")
+        })?,
+    };
+    let mut out = Extracted {
+        name: archive_name(path),
+        fonts: Vec::new(),
+        docs: Vec::new(),
+        dir: TempDir::new()?,
+        dirs: HashMap::new(),
+    };
+    let file = File::open(path)?;
+
+    match format {
+        Format::Zip => {
+            let mut zip = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i).map_err(io::Error::other)?;
+                if !entry.is_file() {
+                    continue;
+                }
+                // enclosed_name() rejects absolute paths and `..` components
+                let Some(name) = entry.enclosed_name() else { continue };
+                out.write_entry(path, &name, &mut entry, with_docs)?;
+            }
+        }
+        Format::Tar => extract_tar(path, file, with_docs, &mut out)?,
+        Format::TarGz => extract_tar(path, GzDecoder::new(BufReader::new(file)), with_docs, &mut out)?,
+        Format::TarXz => {
+            // lzma-rs only decompresses into a writer, so spill the tarball to disk
+            let tar_path = out.dir.path().join("archive.tar");
+            let mut tar_file = File::options().read(true).write(true).create_new(true).open(&tar_path)?;
+            lzma_rs::xz_decompress(&mut BufReader::new(file), &mut tar_file)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}")))?;
+            tar_file.seek(SeekFrom::Start(0))?;
+            extract_tar(path, BufReader::new(&tar_file), with_docs, &mut out)?;
+            drop(tar_file);
+            fs::remove_file(&tar_path)?;
+        }
+    }
+    Ok(out)
+}
+
+fn extract_tar(path: &Path, reader: impl Read, with_docs: bool, out: &mut Extracted) -> io::Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.into_owned();
+        if name.is_absolute() || name.components().any(|c| c == std::path::Component::ParentDir) {
+            continue;
+        }
+        out.write_entry(path, &name, &mut entry, with_docs)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use zip::write::SimpleFileOptions;
+
+    use crate::sfnt::{Font, Table, build_sfnt};
+
+    fn zip_archive(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    // The tar builder refuses `..` itself, so names go straight into the header
+    fn tar_archive(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut tar = tar::Builder::new(File::create(path).unwrap());
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append(&header, *data).unwrap();
+        }
+        tar.finish().unwrap();
+    }
+
+    fn entries(extracted: &Extracted) -> Vec<String> {
+        extracted.fonts.iter().map(|(_, origin)| origin.display().to_string()).collect()
+    }
+
+    const HOSTILE: &[(&str, &[u8])] = &[
+        ("Fonts/A.ttf", b"a"),
+        ("../Escaped.ttf", b"escaped"),
+        ("Fonts/../../Up.ttf", b"up"),
+        ("/Absolute.ttf", b"absolute"),
+        ("__MACOSX/Fonts/._A.ttf", b"fork"),
+        ("Fonts/._B.ttf", b"appledouble"),
+        ("Fonts/OFL.txt", b"license"),
+        ("Fonts/notes.md", b"notes"),
+    ];
+
+    fn check_hostile(archive: &Path, dir: &TempDir) {
+        let extracted = extract(archive, true).unwrap();
+        let root = extracted.dir.path();
+        // zip reads `/Absolute.ttf` as relative, tar skips it: either way it stays put
+        let names = entries(&extracted);
+        assert_eq!(names[0], archive.join("Fonts/A.ttf").display().to_string());
+        assert!(names[1..].iter().all(|name| name.ends_with("/Absolute.ttf")));
+        assert!(extracted.fonts.iter().all(|(font, _)| font.starts_with(root)));
+        assert_eq!(fs::read(&extracted.fonts[0].0).unwrap(), b"a");
+        assert_eq!(extracted.docs.len(), 1);
+        assert_eq!(fs::read(&extracted.docs[0]).unwrap(), b"license");
+        for escaped in ["Escaped.ttf", "Up.ttf"] {
+            assert!(!dir.path().join(escaped).exists());
+            assert!(!root.join(escaped).exists());
+        }
+        assert!(!Path::new("/Absolute.ttf").exists());
+    }
+
+    #[test]
+    fn zip_extraction_stays_inside_its_dir() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("Fonts.zip");
+        zip_archive(&archive, HOSTILE);
+        check_hostile(&archive, &dir);
+    }
+
+    #[test]
+    fn tar_extraction_stays_inside_its_dir() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("Fonts.tar");
+        tar_archive(&archive, HOSTILE);
+        check_hostile(&archive, &dir);
+    }
+
+    #[test]
+    fn same_names_in_different_dirs_do_not_collide() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("Family.zip");
+        zip_archive(&archive, &[("ttf/Font.ttf", b"ttf"), ("otf/Font.ttf", b"otf"), ("ttf/Font.afm", b"metrics")]);
+        let extracted = extract(&archive, false).unwrap();
+        assert_eq!(extracted.name, "Family");
+        let contents: Vec<_> = extracted.fonts.iter().map(|(path, _)| fs::read(path).unwrap()).collect();
+        assert_eq!(contents, [b"ttf".to_vec(), b"otf".to_vec()]);
+        // Metrics land beside the fonts from the same archive dir, and are no docs
+        assert!(extracted.fonts[0].0.with_extension("afm").exists());
+        assert!(extracted.docs.is_empty());
+    }
+
+    #[test]
+    fn docs_only_on_request() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("Fonts.tar");
+        tar_archive(&archive, &[("Font.ttf", b"font"), ("LICENSE", b"license")]);
+        assert!(extract(&archive, false).unwrap().docs.is_empty());
+        assert_eq!(extract(&archive, true).unwrap().docs.len(), 1);
+    }
+
+    fn open_sans() -> Vec<u8> {
+        crate::woff::decode_woff2(include_bytes!("../testdata/OpenSans-Italic.woff2")).unwrap()
+    }
+
+    // A Windows-platform name table with just a family and a subfamily
+    fn name_table(family: &str) -> Vec<u8> {
+        let strings = [family, "Regular"].map(|s| s.encode_utf16().flat_map(u16::to_be_bytes).collect::<Vec<_>>());
+        let mut table = [0u16, 2, 6 + 2 * 12].iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<_>>();
+        let mut offset = 0;
+        for (id, string) in strings.iter().enumerate() {
+            for v in [3, 1, 0x409, id as u16 + 1, string.len() as u16, offset] {
+                table.extend_from_slice(&v.to_be_bytes());
+            }
+            offset += string.len() as u16;
+        }
+        table.extend(strings.concat());
+        table
+    }
+
+    // Concatenates standalone fonts behind a collection header, moving each
+    // face's table offsets along with it
+    fn collection(faces: &[&[u8]]) -> Vec<u8> {
+        let mut data = b"ttcf".to_vec();
+        data.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        data.extend_from_slice(&(faces.len() as u32).to_be_bytes());
+        let mut position = 12 + 4 * faces.len();
+        for face in faces {
+            data.extend_from_slice(&(position as u32).to_be_bytes());
+            position += face.len();
+        }
+        for face in faces {
+            let start = data.len();
+            data.extend_from_slice(face);
+            for i in 0..sfnt::read_u16(face, 4).unwrap() as usize {
+                let field = start + 12 + 16 * i + 8;
+                let offset = sfnt::read_u32(&data, field).unwrap() + start as u32;
+                data[field..field + 4].copy_from_slice(&offset.to_be_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn splits_collections_into_standalone_faces() {
+        let first = open_sans();
+        let font = Font::parse(&first).unwrap();
+        let tables = font.tables.iter()
+            .map(|rec| {
+                let data = if &rec.tag == b"name" { name_table("Other") } else { font.table(&rec.tag).unwrap().to_vec() };
+                Table { tag: rec.tag, data }
+            })
+            .collect();
+        let second = build_sfnt(font.sfnt_version, tables);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("Fonts.ttc");
+        fs::write(&path, collection(&[&first, &second])).unwrap();
+        let split = split_collection(&path).unwrap();
+
+        let origins: Vec<_> = split.fonts.iter().map(|(_, origin)| origin.display().to_string()).collect();
+        assert_eq!(origins, [0, 1].map(|i| format!("{}#{i}", path.display())));
+        let files: Vec<_> = split.fonts.iter().map(|(font, _)| font.file_name().unwrap().to_owned()).collect();
+        assert_eq!(files, ["Fonts-0.ttf", "Fonts-1.ttf"]);
+        // Both were laid out by build_sfnt to begin with, so nothing moves
+        assert_eq!(fs::read(&split.fonts[0].0).unwrap(), first);
+        assert_eq!(fs::read(&split.fonts[1].0).unwrap(), second);
+        let names = sfnt::read_names(&split.fonts[1].0).unwrap();
+        assert_eq!(names.family.as_deref(), Some("Other"));
+        assert_eq!(names.subfamily.as_deref(), Some("Regular"));
+    }
+
+    #[test]
+    fn refuses_truncated_collections() {
+        let face = open_sans();
+        let data = collection(&[&face]);
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("Short.ttc");
+        fs::write(&path, &data[..data.len() / 2]).unwrap();
+        assert!(split_collection(&path).is_err());
+        fs::write(&path, &data[..14]).unwrap();
+        assert!(split_collection(&path).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+// Keeping fontconfig's cache in step with what was installed or removed.
+// Only the directories that changed are rescanned, all in one fc-cache run,
+// and without -f: fontconfig already notices stale caches by directory mtime.
+// System-wide changes go to the system cache (-s), and when that runs under
+// sudo, the invoking user's cache is refreshed as that user too. Staged trees
+// only ever get a cache of their own, through --sysroot.
+
+use std::env;
+use std::fs::File;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use nix::unistd::{User, geteuid};
+
+use crate::error::{Error, Result};
+use crate::layout::Scope;
+
+/// Variables that locate a user's fontconfig configuration, cache and fonts.
+/// They reach a root process only for refreshing the invoking user's cache.
+pub const USER_CACHE_ENV: [&str; 3] = ["XDG_CONFIG_HOME", "XDG_CACHE_HOME", "XDG_DATA_HOME"];
+
+/// When to run `fc-cache` after an install or uninstall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheRefresh {
+    /// Only when fonts were actually added, replaced or removed.
+    OnChange,
+    Always,
+    Never,
+}
+
+impl CacheRefresh {
+    pub fn name(self) -> &'static str {
+        match self {
+            CacheRefresh::OnChange => "on-change",
+            CacheRefresh::Always => "always",
+            CacheRefresh::Never => "never",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CacheRefresh> {
+        match name {
+            "on-change" => Some(CacheRefresh::OnChange),
+            "always" => Some(CacheRefresh::Always),
+            "never" => Some(CacheRefresh::Never),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn wanted(self, changed: bool) -> bool {
+        match self {
+            CacheRefresh::OnChange => changed,
+            CacheRefresh::Always => true,
+            CacheRefresh::Never => false,
+        }
+    }
+}
+
+/// What a cache refresh did.
+#[derive(Debug, Clone)]
+pub struct Refresh {
+    /// Whose cache: the system one (`fc-cache -s`) or the current user's.
+    pub scope: Scope,
+    /// Directories rescanned; empty when fontconfig rescanned all of its own.
+    pub dirs: Vec<PathBuf>,
+    /// Whether caches were rebuilt from scratch (`fc-cache -f`).
+    pub forced: bool,
+    /// fc-cache is not installed, so the directories were only marked stale
+    /// for fontconfig to rescan the next time a program loads fonts.
+    pub fallback: bool,
+    /// The user who ran fontize through sudo (or doas, pkexec), whose cache
+    /// was refreshed too.
+    pub invoking_user: Option<String>,
+    pub elapsed: Duration,
+}
+
+impl Refresh {
+    // Folds a refresh of another scope into this one
+    pub(crate) fn merge(&mut self, other: Refresh) {
+        if other.scope == Scope::System {
+            self.scope = Scope::System;
+        }
+        self.dirs.extend(other.dirs);
+        self.forced |= other.forced;
+        self.fallback |= other.fallback;
+        self.invoking_user = self.invoking_user.take().or(other.invoking_user);
+        self.elapsed += other.elapsed;
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheStatus {
+    /// Nothing changed, or refreshing is turned off.
+    Skipped,
+    /// A dry run that would have refreshed these directories.
+    Pending(Vec<PathBuf>),
+    Refreshed(Refresh),
+    Failed(Error),
+}
+
+/// Refreshes fontconfig's cache of `scope` for `dirs`, or for every
+/// directory fontconfig knows when `dirs` is empty; `force` discards existing
+/// caches first.
+pub fn refresh_font_cache(scope: Scope, dirs: &[PathBuf], force: bool) -> Result<Refresh> {
+    let dirs = refresh_dirs(dirs);
+    let started = Instant::now();
+    let mut refresh = Refresh { scope, dirs, forced: force, fallback: false, invoking_user: None, elapsed: Duration::ZERO };
+    let invoking_user = invoking_user().filter(|_| scope == Scope::System);
+    let mut command = fc_cache(&refresh, scope == Scope::System);
+    // Root's fc-cache reads root's fontconfig configuration, never the user's
+    if invoking_user.is_some() {
+        for var in USER_CACHE_ENV {
+            command.env_remove(var);
+        }
+    }
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(_) => return Err(Error::CacheRefresh("fc-cache returned non-zero status".into())),
+        Err(_) if !refresh.dirs.is_empty() => {
+            for dir in &refresh.dirs {
+                mark_stale(dir)?;
+            }
+            refresh.forced = false;
+            refresh.fallback = true;
+            refresh.elapsed = started.elapsed();
+            return Ok(refresh);
+        }
+        Err(_) => {
+            return Err(Error::CacheRefresh(
+                "fc-cache not found. Install fontconfig or refresh cache manually".into()
+            ));
+        }
+    }
+
+    // Under sudo, root's own user cache is beside the point
+    if let Some(user) = invoking_user {
+        let status = fc_cache(&refresh, false)
+            .uid(user.uid.as_raw())
+            .gid(user.gid.as_raw())
+            .env("HOME", &user.dir)
+            .env("USER", &user.name)
+            .env("LOGNAME", &user.name)
+            .status();
+        if !status.is_ok_and(|status| status.success()) {
+            return Err(Error::CacheRefresh(format!("could not refresh the font cache of {}", user.name)));
+        }
+        refresh.invoking_user = Some(user.name);
+    }
+    refresh.elapsed = started.elapsed();
+    Ok(refresh)
+}
+
+/// Builds the font cache of a tree staged under `root` for `dirs` inside it
+/// with `fc-cache --sysroot`, so the caches land in the tree as well; the
+/// host's own caches are left alone. Without fc-cache this fails: marking
+/// directories stale would spoil reproducible mtimes.
+pub fn refresh_staged_cache(root: &Path, scope: Scope, dirs: &[PathBuf], force: bool) -> Result<Refresh> {
+    let dirs = refresh_dirs(dirs);
+    let started = Instant::now();
+    let mut command = Command::new("fc-cache");
+    if force {
+        command.arg("-f");
+    }
+    if scope == Scope::System {
+        command.arg("-s");
+    }
+    command.arg("--sysroot").arg(root);
+    // fc-cache expects directories as the target system sees them
+    command.args(dirs.iter().map(|dir| Path::new("/").join(dir.strip_prefix(root).unwrap_or(dir))));
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(_) => return Err(Error::CacheRefresh("fc-cache returned non-zero status".into())),
+        Err(_) => return Err(Error::CacheRefresh("fc-cache not found. Install fontconfig to build the staged cache".into())),
+    }
+    Ok(Refresh { scope, dirs, forced: force, fallback: false, invoking_user: None, elapsed: started.elapsed() })
+}
+
+fn fc_cache(refresh: &Refresh, system_only: bool) -> Command {
+    let mut command = Command::new("fc-cache");
+    if refresh.forced {
+        command.arg("-f");
+    }
+    if system_only {
+        command.arg("-s");
+    }
+    command.args(&refresh.dirs);
+    command
+}
+
+// The user behind sudo, doas or pkexec, when we are root because of them
+fn invoking_user() -> Option<User> {
+    if !geteuid().is_root() {
+        return None;
+    }
+    let user = match env::var("SUDO_USER").or_else(|_| env::var("DOAS_USER")) {
+        Ok(name) => User::from_name(&name).ok().flatten(),
+        Err(_) => User::from_uid(env::var("PKEXEC_UID").ok()?.parse::<u32>().ok()?.into()).ok().flatten(),
+    };
+    user.filter(|user| !user.uid.is_root())
+}
+
+/// The directories an fc-cache run needs to cover `dirs`: those removed
+/// since are replaced by their closest surviving ancestor, and any that sit
+/// inside another are dropped, as fc-cache recurses.
+pub(crate) fn refresh_dirs(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let existing: Vec<PathBuf> = dirs.iter()
+        .filter_map(|dir| dir.ancestors().find(|d| d.is_dir()).map(Path::to_path_buf))
+        .collect();
+    outermost_dirs(existing)
+}
+
+/// `dirs` without those inside another, as fc-cache recurses: what a
+/// refresh will cover once the directories exist.
+pub(crate) fn outermost_dirs(mut dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    dirs.sort();
+    dirs.dedup();
+    let mut covered: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        // Sorted, so an ancestor always comes before what it contains
+        if !covered.iter().any(|outer| dir.starts_with(outer)) {
+            covered.push(dir);
+        }
+    }
+    covered
+}
+
+// fontconfig trusts a directory's cache while the recorded mtime matches;
+// bumping it makes the next program that loads fonts rescan the directory
+fn mark_stale(dir: &Path) -> Result<()> {
+    File::open(dir)?.set_modified(SystemTime::now())?;
+    Ok(())
+}
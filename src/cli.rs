@@ -0,0 +1,221 @@
+// Command-line interface definition. Kept apart from main.rs so completions
+// and the man page are generated from exactly what is parsed.
+
+use std::path::PathBuf;
+
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use fontize::{ConflictPolicy, FontKind};
+
+use crate::escalate::Backend;
+
+const EXIT_STATUS: &str = "\
+Exit status:
+  0   success
+  1   I/O error, failed verification, or several kinds of failure in one batch
+  2   invalid command line or configuration
+  3   no fonts found to install, or no installed font matches
+  4   unknown font format
+  5   validation failed
+  6   conflict with an installed font
+  7   permission denied
+  8   moving a file across filesystems failed
+  9   fonts were installed or removed, but refreshing the font cache failed
+  10  could not escalate privileges";
+
+/// Install, inspect and remove fonts on Linux.
+#[derive(Debug, Parser)]
+#[command(name = "fontize", version, after_help = EXIT_STATUS)]
+pub struct Cli {
+    /// Work on the fonts of the current user (~/.local/share/fonts); same as --scope=user
+    #[arg(long, global = true, conflicts_with_all = ["system", "scope"])]
+    pub user: bool,
+
+    /// Work on system-wide fonts (/usr/share/fonts); same as --scope=system
+    #[arg(long, global = true, conflicts_with = "scope")]
+    pub system: bool,
+
+    /// Whose fonts to work on; auto means system-wide when running as root
+    /// or when root rights need no password, the user's otherwise. Given
+    /// explicitly, `list` shows only fonts of that scope [default: system,
+    /// unless configured]
+    #[arg(long, global = true, value_name = "SCOPE")]
+    pub scope: Option<ScopeChoice>,
+
+    /// Only print errors and warnings
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print details about every font handled
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Leave fontconfig's cache alone after installing or removing fonts,
+    /// whatever cache_refresh is configured to
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// How to get root rights when a system-wide operation is denied
+    /// [default: auto, unless configured]
+    #[arg(long, global = true, value_name = "BACKEND")]
+    pub escalate: Option<Backend>,
+
+    /// Never try to get root rights; same as --escalate=none
+    #[arg(long, global = true, conflicts_with = "escalate")]
+    pub no_escalate: bool,
+
+    /// Never ask for a password: get root rights only where none is needed,
+    /// and fail otherwise
+    #[arg(long, global = true)]
+    pub non_interactive: bool,
+
+    /// Install into, and look at, a staging tree standing in for / (as for
+    /// packages and images) instead of the live system; defaults to
+    /// $DESTDIR. Nothing is escalated, the host's font cache is left alone,
+    /// and written files get fixed owners, modes and $SOURCE_DATE_EPOCH times
+    #[arg(long, global = true, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// With --root, build the staged tree's own font cache (fc-cache --sysroot)
+    #[arg(long, global = true)]
+    pub sysroot_cache: bool,
+
+    /// Output format; ndjson writes one JSON record per line as fonts are handled
+    #[arg(short, long, global = true, value_name = "FORMAT", default_value = "text")]
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Install font files, directories of fonts and font archives
+    Install(InstallArgs),
+    /// Remove installed fonts by file name, family or path
+    Uninstall {
+        /// File name, stem, family name or path of the fonts to remove
+        query: String,
+    },
+    /// List installed fonts, whether or not fontize installed them
+    List(ListArgs),
+    /// Show what a font file contains, and what fontize recorded about it
+    /// if it installed it
+    Info {
+        /// A font file, or the file name, stem or family name of installed fonts
+        query: String,
+    },
+    /// Find installed fonts whose file or family name contains a string
+    Search {
+        query: String,
+    },
+    /// Check installed fonts against the checksums recorded at install time
+    Verify,
+    /// Manage fontconfig's font cache: the user's, or the system one with --system
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// Print a shell completion script
+    Completions {
+        shell: Shell,
+    },
+    /// Print the man page in roff format
+    Man,
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Write files for an unprivileged `fontize install`; run as root by it
+    #[command(hide = true)]
+    Helper,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// Rescan font directories whose cache is out of date
+    Refresh {
+        /// Directories to rescan; all of fontconfig's when none are given
+        dirs: Vec<PathBuf>,
+    },
+    /// Throw the cache away and rescan every font (fc-cache -f)
+    Rebuild {
+        /// Directories to rebuild; all of fontconfig's when none are given
+        dirs: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print every setting in effect and where its value comes from: built
+    /// in, /etc/fontize/config.toml, ~/.config/fontize/config.toml, a
+    /// FONTIZE_* variable or a flag
+    Show,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ListArgs {
+    /// Only fonts of this family
+    #[arg(long)]
+    pub family: Option<String>,
+
+    /// Only fonts in this format
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_parser = PossibleValuesParser::new(["otf", "ttf", "woff", "woff2", "ttc", "pcf", "bdf", "type1"])
+            .map(|name| FontKind::from_name(&name).expect("listed above")),
+    )]
+    pub format: Option<FontKind>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct InstallArgs {
+    /// Font files, directories and archives (.zip, .tar, .tar.gz, .tar.xz)
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Move font files into place instead of copying them
+    #[arg(long = "move")]
+    pub move_files: bool,
+
+    /// Copy font files into place, even if configured to move them
+    #[arg(long, conflicts_with = "move_files")]
+    pub copy: bool,
+
+    /// Also install license/readme files found in archives
+    #[arg(long)]
+    pub with_docs: bool,
+
+    /// Install each face of a TTC/OTC collection as its own font
+    #[arg(long)]
+    pub split: bool,
+
+    /// Install WOFF/WOFF2 files as they are instead of converting to TTF/OTF
+    #[arg(long)]
+    pub no_decompress: bool,
+
+    /// When a different build of the same font is already installed: keep it,
+    /// overwrite it unless it is newer, install alongside it, or stop
+    /// [default: fail, unless configured]
+    #[arg(
+        long,
+        value_name = "POLICY",
+        value_parser = PossibleValuesParser::new(["skip", "replace", "rename", "fail"])
+            .map(|name| ConflictPolicy::from_name(&name).expect("listed above")),
+    )]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// Install fonts even if validation finds structural errors
+    #[arg(long)]
+    pub force: bool,
+
+    /// Print what would be installed where, without changing anything;
+    /// FORMAT overrides --output
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true)]
+    pub dry_run: Option<Option<OutputFormat>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat { Text, Json, Ndjson }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScopeChoice { Auto, User, System }
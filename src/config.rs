@@ -0,0 +1,356 @@
+// Defaults for everything the command line can be told, in layers: built-in
+// values, then /etc/fontize/config.toml, then the user's config.toml, then
+// FONTIZE_* variables, then flags. Each setting remembers the layer it last
+// came from, for `fontize config show`.
+//
+// The files are the flat part of TOML: `key = value` lines with strings,
+// booleans and comments, and no tables.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use fontize::{
+    CacheRefresh, ConflictPolicy, DEFAULT_TEMPLATE, Error, Layout, Result, check_template, system_fonts_base,
+    user_fonts_base,
+};
+use serde_json::{Value, json};
+
+use crate::cli::{Cli, Command, ScopeChoice};
+use crate::escalate::Backend;
+
+pub const SYSTEM_CONFIG: &str = "/etc/fontize/config.toml";
+
+// Every key, with the variable that overrides it
+const KEYS: [(&str, &str); 8] = [
+    ("scope", "FONTIZE_SCOPE"),
+    ("user_base", "FONTIZE_USER_BASE"),
+    ("system_base", "FONTIZE_SYSTEM_BASE"),
+    ("layout", "FONTIZE_LAYOUT"),
+    ("on_conflict", "FONTIZE_ON_CONFLICT"),
+    ("move", "FONTIZE_MOVE"),
+    ("cache_refresh", "FONTIZE_CACHE_REFRESH"),
+    ("escalate", "FONTIZE_ESCALATE"),
+];
+
+/// Where a setting's value came from.
+#[derive(Debug, Clone)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Env(&'static str),
+    Flag(&'static str),
+}
+
+impl Origin {
+    fn json(&self) -> Value {
+        match self {
+            Origin::Default => json!({ "origin": "default" }),
+            Origin::File(path) => json!({ "origin": "file", "source": path }),
+            Origin::Env(var) => json!({ "origin": "env", "source": var }),
+            Origin::Flag(flag) => json!({ "origin": "flag", "source": flag }),
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Env(var) => write!(f, "${var}"),
+            Origin::Flag(flag) => write!(f, "{flag}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting<T> {
+    pub value: T,
+    pub origin: Origin,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T) -> Setting<T> {
+        Setting { value, origin: Origin::Default }
+    }
+
+    fn set(&mut self, value: T, origin: Origin) {
+        self.value = value;
+        self.origin = origin;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub scope: Setting<ScopeChoice>,
+    pub user_base: Setting<PathBuf>,
+    pub system_base: Setting<PathBuf>,
+    pub layout: Setting<String>,
+    pub on_conflict: Setting<ConflictPolicy>,
+    pub move_files: Setting<bool>,
+    pub cache_refresh: Setting<CacheRefresh>,
+    pub escalate: Setting<Backend>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            scope: Setting::new(ScopeChoice::System),
+            user_base: Setting::new(user_fonts_base()),
+            system_base: Setting::new(system_fonts_base()),
+            layout: Setting::new(DEFAULT_TEMPLATE.into()),
+            on_conflict: Setting::new(ConflictPolicy::Fail),
+            move_files: Setting::new(false),
+            cache_refresh: Setting::new(CacheRefresh::OnChange),
+            escalate: Setting::new(Backend::Auto),
+        }
+    }
+}
+
+impl Config {
+    /// Built-in values overridden by both config files and the environment.
+    pub fn load() -> Result<Config> {
+        let mut config = Config::default();
+        config.read_file(Path::new(SYSTEM_CONFIG))?;
+        if let Some(dir) = dirs::config_dir() {
+            config.read_file(&dir.join("fontize/config.toml"))?;
+        }
+        for (key, var) in KEYS {
+            if let Some(value) = env::var_os(var) {
+                config.set(key, &value.to_string_lossy(), Origin::Env(var))
+                    .map_err(|e| Error::Config(format!("{var}: {e}")))?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Built-in values overridden by /etc/fontize/config.toml alone: what
+    /// root has configured, whoever runs fontize.
+    pub fn system() -> Result<Config> {
+        let mut config = Config::default();
+        config.read_file(Path::new(SYSTEM_CONFIG))?;
+        Ok(config)
+    }
+
+    /// Lets the flags on the command line have the last word.
+    pub fn apply_flags(&mut self, cli: &Cli) {
+        if let Some(scope) = cli.scope {
+            self.scope.set(scope, Origin::Flag("--scope"));
+        } else if cli.user {
+            self.scope.set(ScopeChoice::User, Origin::Flag("--user"));
+        } else if cli.system {
+            self.scope.set(ScopeChoice::System, Origin::Flag("--system"));
+        }
+        if cli.no_escalate {
+            self.escalate.set(Backend::None, Origin::Flag("--no-escalate"));
+        } else if let Some(backend) = cli.escalate {
+            self.escalate.set(backend, Origin::Flag("--escalate"));
+        }
+        if cli.no_cache {
+            self.cache_refresh.set(CacheRefresh::Never, Origin::Flag("--no-cache"));
+        }
+        if let Command::Install(args) = &cli.command {
+            if let Some(policy) = args.on_conflict {
+                self.on_conflict.set(policy, Origin::Flag("--on-conflict"));
+            }
+            if args.move_files {
+                self.move_files.set(true, Origin::Flag("--move"));
+            } else if args.copy {
+                self.move_files.set(false, Origin::Flag("--copy"));
+            }
+        }
+    }
+
+    pub fn layout(&self, root: Option<PathBuf>) -> Layout {
+        Layout {
+            user_base: self.user_base.value.clone(),
+            system_base: self.system_base.value.clone(),
+            template: self.layout.value.clone(),
+            root,
+        }
+    }
+
+    /// Every setting as `(key, value, origin)`, in the order of the file format.
+    pub fn settings(&self) -> Vec<(&'static str, String, &Origin)> {
+        let scope = self.scope.value.to_possible_value().expect("no skipped variants");
+        vec![
+            ("scope", scope.get_name().to_string(), &self.scope.origin),
+            ("user_base", self.user_base.value.display().to_string(), &self.user_base.origin),
+            ("system_base", self.system_base.value.display().to_string(), &self.system_base.origin),
+            ("layout", self.layout.value.clone(), &self.layout.origin),
+            ("on_conflict", self.on_conflict.value.name().to_string(), &self.on_conflict.origin),
+            ("move", self.move_files.value.to_string(), &self.move_files.origin),
+            ("cache_refresh", self.cache_refresh.value.name().to_string(), &self.cache_refresh.origin),
+            ("escalate", self.escalate.value.name().to_string(), &self.escalate.origin),
+        ]
+    }
+
+    /// One setting for machine-readable output.
+    pub fn setting_json(key: &str, value: &str, origin: &Origin) -> Value {
+        let mut setting = origin.json();
+        setting["key"] = json!(key);
+        setting["value"] = json!(value);
+        setting
+    }
+
+    fn read_file(&mut self, path: &Path) -> Result<()> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(Error::Config(format!("{}: {e}", path.display()))),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let at = |e: String| Error::Config(format!("{}:{}: {e}", path.display(), number + 1));
+            if let Some((key, value)) = parse_line(line).map_err(at)? {
+                self.set(key, &value, Origin::File(path.to_path_buf())).map_err(at)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str, origin: Origin) -> std::result::Result<(), String> {
+        let invalid = || format!("invalid {key} {value:?}");
+        match key {
+            "scope" => self.scope.set(ScopeChoice::from_str(value, false).map_err(|_| invalid())?, origin),
+            "user_base" => self.user_base.set(base_dir(value).ok_or_else(invalid)?, origin),
+            "system_base" => self.system_base.set(base_dir(value).ok_or_else(invalid)?, origin),
+            "layout" => {
+                if let Err(Error::Config(e)) = check_template(value) {
+                    return Err(e);
+                }
+                self.layout.set(value.to_string(), origin);
+            }
+            "on_conflict" => self.on_conflict.set(ConflictPolicy::from_name(value).ok_or_else(invalid)?, origin),
+            "move" => {
+                let move_files = match value {
+                    "true" | "yes" | "1" => true,
+                    "false" | "no" | "0" => false,
+                    _ => return Err(invalid()),
+                };
+                self.move_files.set(move_files, origin);
+            }
+            "cache_refresh" => self.cache_refresh.set(CacheRefresh::from_name(value).ok_or_else(invalid)?, origin),
+            "escalate" => self.escalate.set(Backend::from_str(value, false).map_err(|_| invalid())?, origin),
+            _ => return Err(format!("unknown setting {key:?}")),
+        }
+        Ok(())
+    }
+}
+
+// Absolute, after expanding a leading `~/`
+fn base_dir(value: &str) -> Option<PathBuf> {
+    let path = match value.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(env::var_os("HOME")?).join(rest),
+        None => PathBuf::from(value),
+    };
+    path.is_absolute().then_some(path)
+}
+
+// `key = value`, with the value unquoted; None for blank and comment lines
+fn parse_line(line: &str) -> std::result::Result<Option<(&str, String)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    if line.starts_with('[') {
+        return Err("tables are not supported; settings go at the top level".into());
+    }
+    let (key, value) = line.split_once('=').ok_or("expected key = value")?;
+    let key = key.trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!("invalid key {key:?}"));
+    }
+    let value = value.trim_start();
+    let (value, rest) = if let Some(quoted) = value.strip_prefix('"') {
+        basic_string(quoted)?
+    } else if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted.find('\'').ok_or("unterminated string")?;
+        (quoted[..end].to_string(), &quoted[end + 1..])
+    } else {
+        let end = value.find(|c: char| c.is_whitespace() || c == '#').unwrap_or(value.len());
+        match &value[..end] {
+            word @ ("true" | "false") => (word.to_string(), &value[end..]),
+            _ => return Err("expected a quoted string, true or false".into()),
+        }
+    };
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected {rest:?} after the value"));
+    }
+    Ok(Some((key, value)))
+}
+
+// The rest of a "…" string after its opening quote, with escapes resolved,
+// and whatever follows the closing quote
+fn basic_string(quoted: &str) -> std::result::Result<(String, &str), String> {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &quoted[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(other) => return Err(format!("unsupported escape \\{other}")),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err("unterminated string".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(line: &str) -> std::result::Result<String, String> {
+        parse_line(line).map(|parsed| parsed.expect("a setting").1)
+    }
+
+    #[test]
+    fn skips_blank_and_comment_lines() {
+        assert_eq!(parse_line(""), Ok(None));
+        assert_eq!(parse_line("   "), Ok(None));
+        assert_eq!(parse_line("  # scope = \"user\""), Ok(None));
+    }
+
+    #[test]
+    fn reads_strings_and_booleans() {
+        assert_eq!(parse_line("scope = \"user\""), Ok(Some(("scope", "user".to_string()))));
+        assert_eq!(parse_line("  move=true  # always"), Ok(Some(("move", "true".to_string()))));
+        assert_eq!(value("layout = '{family}\\n'"), Ok("{family}\\n".to_string()));
+        assert_eq!(value("layout = \"a#b\" # not part of it"), Ok("a#b".to_string()));
+        assert_eq!(value("layout = \"\""), Ok(String::new()));
+    }
+
+    #[test]
+    fn resolves_escapes_in_basic_strings() {
+        assert_eq!(value(r#"user_base = "C:\\fonts \"mine\"""#), Ok("C:\\fonts \"mine\"".to_string()));
+        assert_eq!(value(r#"layout = "a\tb\nc""#), Ok("a\tb\nc".to_string()));
+        assert_eq!(basic_string(r#"x" # rest"#), Ok(("x".to_string(), " # rest")));
+        assert_eq!(basic_string(r#"x\" still open"#), Err("unterminated string".to_string()));
+        assert_eq!(basic_string("x\\"), Err("unterminated string".to_string()));
+        assert!(value(r#"layout = "\u0041""#).unwrap_err().contains("unsupported escape"));
+    }
+
+    #[test]
+    fn rejects_what_it_does_not_read() {
+        assert!(value("[fontize]").unwrap_err().contains("tables"));
+        assert!(value("[[fontize]]").unwrap_err().contains("tables"));
+        assert!(value("scope").unwrap_err().contains("key = value"));
+        assert!(value("\"scope\" = \"user\"").unwrap_err().contains("invalid key"));
+        assert!(value("= \"user\"").unwrap_err().contains("invalid key"));
+        assert!(value("move = 1").unwrap_err().contains("true or false"));
+        assert!(value("scope = user").unwrap_err().contains("quoted string"));
+        assert!(value("scope = 'user").unwrap_err().contains("unterminated"));
+        assert!(value("scope = \"user\" \"system\"").unwrap_err().contains("after the value"));
+        assert!(value("move = true false").unwrap_err().contains("after the value"));
+    }
+}
@@ -0,0 +1,120 @@
+// Everything that can go wrong, by category. Each category has a stable exit
+// code so scripts driving the CLI can tell them apart.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Nothing to install, or no installed font matches.
+    NotFound(String),
+    /// Neither the extension nor the contents look like a font fontize knows.
+    UnknownFormat(PathBuf),
+    /// Structural errors found before installing; see `Installer::force`.
+    Validation(Vec<String>),
+    /// The destination holds a different build of the same font.
+    Conflict {
+        destination: PathBuf,
+        installed: Option<String>,
+        installed_version: Option<String>,
+        version: Option<String>,
+    },
+    PermissionDenied(io::Error),
+    /// Moving across filesystems, and the copy that stands in for the rename
+    /// failed too.
+    CrossDevice { from: PathBuf, to: PathBuf, source: io::Error },
+    CacheRefresh(String),
+    Escalation(String),
+    /// A configuration file or variable that cannot be used.
+    Config(String),
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Process exit code for this category of error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) => 1,
+            Error::Config(_) => 2,
+            Error::NotFound(_) => 3,
+            Error::UnknownFormat(_) => 4,
+            Error::Validation(_) => 5,
+            Error::Conflict { .. } => 6,
+            Error::PermissionDenied(_) => 7,
+            Error::CrossDevice { .. } => 8,
+            Error::CacheRefresh(_) => 9,
+            Error::Escalation(_) => 10,
+        }
+    }
+
+    /// Short, stable name of the category, for machine-readable output.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "not_found",
+            Error::UnknownFormat(_) => "unknown_format",
+            Error::Validation(_) => "validation",
+            Error::Conflict { .. } => "conflict",
+            Error::PermissionDenied(_) => "permission_denied",
+            Error::CrossDevice { .. } => "cross_device",
+            Error::CacheRefresh(_) => "cache_refresh",
+            Error::Escalation(_) => "escalation",
+            Error::Config(_) => "config",
+            Error::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(message) => write!(f, "{message}"),
+            Error::UnknownFormat(_) => write!(f, "Unknown font format (not OTF/TTF/TTC/WOFF/WOFF2/PCF/BDF/Type 1)"),
+            Error::Validation(errors) => {
+                write!(f, "Validation failed: {} (use --force to install anyway)", errors.join("; "))
+            }
+            Error::Conflict { destination, installed, installed_version, version } => {
+                let or_unknown = |v: &Option<String>| v.clone().unwrap_or_else(|| "unknown version".into());
+                write!(
+                    f,
+                    "Conflict: {} already holds {} ({}, installing {}); pass --on-conflict=skip|replace|rename",
+                    destination.display(),
+                    installed.as_deref().unwrap_or("a different build"),
+                    or_unknown(installed_version),
+                    or_unknown(version),
+                )
+            }
+            Error::PermissionDenied(e) => write!(f, "{e}"),
+            Error::CrossDevice { from, to, source } => write!(
+                f,
+                "Could not move {} to {} across filesystems: {source}",
+                from.display(),
+                to.display()
+            ),
+            Error::CacheRefresh(message) => write!(f, "Font cache refresh failed: {message}"),
+            Error::Escalation(message) => write!(f, "{message}"),
+            Error::Config(message) => write!(f, "Bad configuration: {message}"),
+            Error::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::PermissionDenied(e) | Error::CrossDevice { source: e, .. } | Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(e),
+            _ => Error::Io(e),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+// Getting root rights for system-wide operations: installs start the
+// privileged `fontize helper` to write what they planned, anything else
+// denied is rerun whole. The tool doing it is pluggable; whichever is used,
+// the command goes through env(1) so the XDG and locale variables that decide
+// where fonts, manifests and messages go survive the trip, whatever the
+// tool's own env policy.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, IsTerminal};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+use fontize::{Error, Result, USER_CACHE_ENV};
+
+// Set in the rerun, so it cannot loop
+const ELEVATED: &str = "INSTALL_FONT_ELEVATED";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// The first of sudo, doas, run0 and pkexec that is installed; pkexec
+    /// first in a graphical session without a terminal
+    Auto,
+    Sudo,
+    Doas,
+    Pkexec,
+    Run0,
+    /// Never escalate; fail with the permission error instead
+    None,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Auto => "auto",
+            Backend::Sudo => "sudo",
+            Backend::Doas => "doas",
+            Backend::Pkexec => "pkexec",
+            Backend::Run0 => "run0",
+            Backend::None => "none",
+        }
+    }
+
+    /// Whether denied system-wide operations are retried with root rights at all.
+    pub fn enabled(self) -> bool {
+        self != Backend::None && env::var_os(ELEVATED).is_none()
+    }
+
+    // The backends to try, in order; pkexec always asks, so it is left out
+    // when nobody is there to answer
+    fn candidates(self, interactive: bool) -> Vec<Backend> {
+        let candidates = match self {
+            Backend::Auto if interactive && graphical_session() => {
+                vec![Backend::Pkexec, Backend::Sudo, Backend::Doas, Backend::Run0]
+            }
+            Backend::Auto => vec![Backend::Sudo, Backend::Doas, Backend::Run0, Backend::Pkexec],
+            Backend::None => Vec::new(),
+            backend => vec![backend],
+        };
+        candidates.into_iter().filter(|&backend| interactive || backend != Backend::Pkexec).collect()
+    }
+
+    /// The backend that would run, and where it is installed.
+    pub fn resolve(self, interactive: bool) -> Result<(Backend, PathBuf)> {
+        if self == Backend::None {
+            return Err(Error::Escalation("Privilege escalation is turned off".into()));
+        }
+        let candidates = self.candidates(interactive);
+        if candidates.is_empty() {
+            return Err(Error::Escalation(format!("{} cannot run without asking for a password", self.name())));
+        }
+        candidates.iter()
+            .find_map(|&backend| find_program(backend.name()).map(|path| (backend, path)))
+            .ok_or_else(|| {
+                let tried = candidates.iter().map(|backend| backend.name()).collect::<Vec<_>>().join(", ");
+                Error::Escalation(format!(
+                    "No privilege escalation tool found (looked for {tried}); run fontize as root or install with --user"
+                ))
+            })
+    }
+
+    /// The first backend that gets root rights without asking for a
+    /// password, e.g. through a NOPASSWD sudoers rule.
+    pub fn passwordless(self) -> Option<Backend> {
+        let truth = find_program("true").unwrap_or_else(|| PathBuf::from("/bin/true"));
+        self.candidates(false).into_iter().find(|&backend| {
+            find_program(backend.name()).is_some_and(|program| {
+                Command::new(program)
+                    .args(backend.no_prompt())
+                    .arg(&truth)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .is_ok_and(|status| status.success())
+            })
+        })
+    }
+
+    // Makes the tool fail rather than ask for a password
+    fn no_prompt(self) -> &'static [&'static str] {
+        match self {
+            Backend::Sudo | Backend::Doas => &["-n"],
+            Backend::Run0 => &["--no-ask-password"],
+            _ => &[],
+        }
+    }
+}
+
+fn graphical_session() -> bool {
+    (env::var_os("DISPLAY").is_some() || env::var_os("WAYLAND_DISPLAY").is_some()) && !io::stdin().is_terminal()
+}
+
+fn find_program(name: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.metadata().is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0))
+}
+
+// Variables the rerun needs to see the same fonts, manifests, configuration
+// and language
+fn preserved_env() -> Vec<OsString> {
+    let mut vars: Vec<OsString> = env::vars_os()
+        .filter(|(key, _)| {
+            let key = key.to_string_lossy();
+            key.starts_with("XDG_") || key.starts_with("LC_") || key.starts_with("FONTIZE_")
+                || key == "LANG" || key == "LANGUAGE"
+        })
+        .map(|(key, value)| [key.as_os_str(), OsStr::new("="), value.as_os_str()].into_iter().collect())
+        .collect();
+    // Root's home holds root's configuration, not the user's
+    if env::var_os("XDG_CONFIG_HOME").is_none()
+        && let Some(dir) = dirs::config_dir()
+    {
+        vars.push([OsStr::new("XDG_CONFIG_HOME="), dir.as_os_str()].into_iter().collect());
+    }
+    vars.sort();
+    vars.push(format!("{ELEVATED}=1").into());
+    vars
+}
+
+// What the helper passes on to the refresh of the invoking user's own cache,
+// and the language: nothing that configures fontize, and nothing that root's
+// fc-cache gets to see
+fn helper_env() -> Vec<OsString> {
+    let mut vars: Vec<OsString> = env::vars_os()
+        .filter(|(key, _)| {
+            let key = key.to_string_lossy();
+            USER_CACHE_ENV.contains(&key.as_ref()) || key.starts_with("LC_") || key == "LANG" || key == "LANGUAGE"
+        })
+        .map(|(key, value)| [key.as_os_str(), OsStr::new("="), value.as_os_str()].into_iter().collect())
+        .collect();
+    vars.sort();
+    vars.push(format!("{ELEVATED}=1").into());
+    vars
+}
+
+// `<tool> env VARS… fontize`, ready for arguments
+fn elevated_command(backend: Backend, program: PathBuf, interactive: bool, vars: Vec<OsString>) -> Result<Vec<OsString>> {
+    // pkexec only runs programs given by absolute path
+    let env_program = find_program("env").unwrap_or_else(|| PathBuf::from("/usr/bin/env"));
+    let mut command = vec![program.into_os_string()];
+    if !interactive {
+        command.extend(backend.no_prompt().iter().map(OsString::from));
+    }
+    command.push(env_program.into_os_string());
+    command.extend(vars);
+    command.push(env::current_exe()?.into_os_string());
+    Ok(command)
+}
+
+/// The command that starts `fontize helper` as root through `backend`;
+/// unless `interactive`, it fails rather than ask for a password.
+pub fn helper_command(backend: Backend, interactive: bool) -> Result<Vec<OsString>> {
+    let (backend, program) = backend.resolve(interactive)?;
+    let mut command = elevated_command(backend, program, interactive, helper_env())?;
+    command.push("helper".into());
+    Ok(command)
+}
+
+/// Reruns fontize with `args` through `backend` and exits with its status.
+pub fn escalate_and_reexec(backend: Backend, interactive: bool, args: &[OsString]) -> Result<()> {
+    // Prevent loops if we’re already elevated
+    if env::var_os(ELEVATED).is_some() {
+        return Err(Error::PermissionDenied(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "Permission denied even after retrying with elevated rights"
+        )));
+    }
+
+    let (backend, program) = backend.resolve(interactive)?;
+    let command = elevated_command(backend, program, interactive, preserved_env())?;
+
+    if interactive {
+        eprintln!("Permission denied. Retrying with {}… (you may be asked to authenticate)", backend.name());
+    } else {
+        eprintln!("Permission denied. Retrying with {}…", backend.name());
+    }
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .args(args)
+        .status();
+
+    match status {
+        Ok(s) => std::process::exit(s.code().unwrap_or(1)),
+        Err(e) => Err(Error::Escalation(
+            format!("Failed to execute {}: {e}", backend.name())
+        )),
+    }
+}
@@ -0,0 +1,191 @@
+// Filesystem helpers for putting fonts in place without leaving half-written
+// files behind.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use nix::unistd::{Gid, Uid, chown, geteuid};
+
+use crate::error::{Error, Result};
+
+// The first of `dest`, `<stem>-1.<ext>`, `<stem>-2.<ext>`… that is not `taken`
+pub(crate) fn unique_path(dest: PathBuf, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    if !taken(&dest) {
+        return dest;
+    }
+    let stem = dest.file_stem().and_then(|s| s.to_str()).unwrap_or("font");
+    let ext = dest.extension().and_then(|e| e.to_str()).unwrap_or("");
+    for i in 1.. {
+        let candidate = if ext.is_empty() {
+            dest.with_file_name(format!("{stem}-{i}"))
+        } else {
+            dest.with_file_name(format!("{stem}-{i}.{ext}"))
+        };
+        if !taken(&candidate) {
+            return candidate;
+        }
+    }
+    unreachable!()
+}
+
+// Whether `path` is one of the numbered copies `unique_path` makes of `dest`
+pub(crate) fn is_numbered_copy(dest: &Path, path: &Path) -> bool {
+    let (Some(stem), Some(name)) = (dest.file_stem().and_then(|s| s.to_str()), path.file_name().and_then(|n| n.to_str()))
+    else {
+        return false;
+    };
+    let ext = dest.extension().and_then(|e| e.to_str()).map(|e| format!(".{e}")).unwrap_or_default();
+    let number = name.strip_prefix(stem).and_then(|rest| rest.strip_prefix('-')?.strip_suffix(ext.as_str()));
+    path.parent() == dest.parent() && number.is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+// The numbered copies of `dest` on disk
+pub(crate) fn numbered_copies(dest: &Path) -> Vec<PathBuf> {
+    let Some(Ok(entries)) = dest.parent().map(fs::read_dir) else { return Vec::new() };
+    let mut copies: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_numbered_copy(dest, path))
+        .collect();
+    copies.sort();
+    copies
+}
+
+// Writes through a temp file in the destination directory, so `dst` either
+// doesn't exist or is complete, never half-written.
+pub(crate) fn write_atomic(src: &mut dyn Read, dst: &Path) -> io::Result<()> {
+    let dir = dst.parent().unwrap_or(Path::new("."));
+    let name = dst.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let tmp = dir.join(format!(".{name}.fontize-{}", std::process::id()));
+
+    let result = (|| {
+        let mut out = File::create(&tmp)?;
+        io::copy(src, &mut out)?;
+        out.sync_all()?;
+        fs::rename(&tmp, dst)?;
+        File::open(dir)?.sync_all()
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+pub(crate) fn copy_atomic(src: &Path, dst: &Path) -> io::Result<()> {
+    write_atomic(&mut File::open(src)?, dst)
+}
+
+pub(crate) fn move_across_fs(src: &Path, dst: &Path) -> Result<()> {
+    match fs::rename(src, dst) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            // Only drop the source once the copy is safely in place
+            copy_atomic(src, dst)
+                .and_then(|()| fs::remove_file(src))
+                .map_err(|source| match source.kind() {
+                    io::ErrorKind::PermissionDenied => Error::PermissionDenied(source),
+                    _ => Error::CrossDevice { from: src.to_path_buf(), to: dst.to_path_buf(), source },
+                })
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(crate) fn set_permissions644(path: &Path) -> io::Result<()> {
+    let mut perms = fs::metadata(path)?.permissions();
+    perms.set_mode(0o644);
+    fs::set_permissions(path, perms)
+}
+
+// The time staged files are stamped with: $SOURCE_DATE_EPOCH, as reproducible
+// builds set it, or else the epoch itself
+pub(crate) fn build_time() -> SystemTime {
+    let secs = std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|secs| secs.parse().ok()).unwrap_or(0);
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+// Makes what a staged install wrote the same on every build: `files` and
+// every directory from theirs up to `root` become root-owned (when we are
+// root, as under fakeroot), mode 644 and 755, and stamped with build_time()
+pub(crate) fn normalize_tree(root: &Path, files: &[PathBuf]) -> io::Result<()> {
+    let mut dirs: Vec<&Path> = Vec::new();
+    for file in files {
+        // Removed files leave only their directories to see to
+        if file.exists() {
+            normalize(file, 0o644)?;
+        }
+        for dir in file.ancestors().skip(1).take_while(|dir| dir.starts_with(root) && *dir != root) {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    for dir in dirs.into_iter().filter(|dir| dir.exists()) {
+        normalize(dir, 0o755)?;
+    }
+    Ok(())
+}
+
+fn normalize(path: &Path, mode: u32) -> io::Result<()> {
+    if geteuid().is_root() {
+        chown(path, Some(Uid::from_raw(0)), Some(Gid::from_raw(0)))?;
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    File::open(path)?.set_modified(build_time())
+}
+
+pub(crate) fn files_under(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            files_under(&entry.path(), out)?;
+        } else if entry.path().is_file() {
+            out.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn remove_empty_dirs(mut dir: &Path, base: &Path) {
+    while dir != base && dir.starts_with(base) {
+        // Fails (and stops) as soon as a directory still has entries
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+        match dir.parent() {
+            Some(parent) => dir = parent,
+            None => break,
+        }
+    }
+}
+
+// Turns a name taken from a font into a single, safe path component.
+pub(crate) fn path_component(name: &str) -> Option<String> {
+    let cleaned = name.chars()
+        .map(|c| if c == '/' || c.is_control() { '_' } else { c })
+        .collect::<String>();
+    let cleaned = cleaned.trim().trim_start_matches('.');
+    (!cleaned.is_empty()).then(|| cleaned.to_string())
+}
+
+// Whether writing at `path` needs more rights than we have, judged by the
+// closest directory that already exists
+pub(crate) fn needs_privileges(path: &Path) -> bool {
+    let mut dir = path;
+    while !dir.exists() {
+        match dir.parent() {
+            Some(parent) => dir = parent,
+            None => return true,
+        }
+    }
+    nix::unistd::access(dir, nix::unistd::AccessFlags::W_OK).is_err()
+}
@@ -0,0 +1,317 @@
+// The privileged half of a system-wide install. The unprivileged process
+// detects, validates and plans every font, then hands a helper running as
+// root a batch of plain steps: write these bytes there, record this manifest
+// line, remove that file, refresh the cache for these directories. The helper
+// never parses a font; all it checks is that every target lies inside the
+// system font base.
+//
+// Steps travel over the helper's stdin, which unlike other descriptors makes
+// it through sudo and friends: one JSON header line per step, a `write`
+// header followed by exactly `len` raw bytes. The helper answers each step
+// with one JSON line on stdout, `{"ok":true,...}` or `{"ok":false,...}`.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use nix::sys::stat::{Mode, umask};
+use serde_json::{Value, json};
+
+use crate::cache;
+use crate::error::{Error, Result};
+use crate::files::{remove_empty_dirs, set_permissions644, write_atomic};
+use crate::layout::Scope;
+use crate::manifest;
+
+#[derive(Debug)]
+pub(crate) enum Step {
+    Write { path: PathBuf, data: Vec<u8> },
+    Record(manifest::Entry),
+    Refresh(Vec<PathBuf>),
+    Remove(PathBuf),
+}
+
+fn utf8(path: &Path) -> io::Result<&str> {
+    path.to_str().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8"))
+}
+
+impl Step {
+    fn send(&self, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Step::Write { path, data } => {
+                writeln!(out, "{}", json!({ "op": "write", "path": utf8(path)?, "len": data.len() }))?;
+                out.write_all(data)
+            }
+            Step::Record(entry) => writeln!(out, "{}", json!({ "op": "record", "entry": entry.to_line() })),
+            Step::Refresh(dirs) => {
+                let dirs = dirs.iter().map(|dir| utf8(dir)).collect::<io::Result<Vec<_>>>()?;
+                writeln!(out, "{}", json!({ "op": "refresh", "dirs": dirs }))
+            }
+            Step::Remove(path) => writeln!(out, "{}", json!({ "op": "remove", "path": utf8(path)? })),
+        }
+    }
+}
+
+/// Runs `steps` through the helper that `command` starts with root rights;
+/// one answer per step, in order.
+pub(crate) fn delegate(command: &[OsString], steps: &[Step]) -> Result<Vec<Result<Value>>> {
+    let (program, args) = command.split_first()
+        .ok_or_else(|| Error::Escalation("No command to run the privileged helper with".into()))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Escalation(format!("Failed to start the privileged helper: {e}")))?;
+    let mut stdin = child.stdin.take().expect("piped");
+    let stdout = child.stdout.take().expect("piped");
+
+    let mut results = thread::scope(|scope| {
+        // Fed from a thread of its own, so neither side blocks on a full pipe
+        scope.spawn(move || {
+            for step in steps {
+                if step.send(&mut stdin).is_err() {
+                    break;                                 // the helper is gone; its status says why
+                }
+            }
+        });
+        BufReader::new(stdout).lines()
+            .map_while(|line| line.ok())
+            .map(|line| answer(&line))
+            .collect::<Vec<_>>()
+    });
+    let status = child.wait()?;
+    while results.len() < steps.len() {
+        results.push(Err(Error::Escalation(format!("The privileged helper stopped early ({status})"))));
+    }
+    Ok(results)
+}
+
+fn answer(line: &str) -> Result<Value> {
+    let answer: Value = serde_json::from_str(line).map_err(|e| Error::Escalation(format!("Bad helper answer: {e}")))?;
+    if answer["ok"].as_bool() == Some(true) {
+        return Ok(answer);
+    }
+    let message = answer["error"].as_str().unwrap_or("unknown error").to_string();
+    Err(match answer["category"].as_str() {
+        Some("permission_denied") => Error::PermissionDenied(io::Error::new(io::ErrorKind::PermissionDenied, message)),
+        Some("cache_refresh") => Error::CacheRefresh(message),
+        _ => Error::Io(io::Error::other(message)),
+    })
+}
+
+fn protocol(message: impl std::fmt::Display) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, format!("Bad helper request: {message}")))
+}
+
+// Only plain paths inside the system font base are touched: files strictly
+// inside it, while a directory to refresh may be the base itself
+fn target(value: &Value, base: &Path, dir: bool) -> Result<PathBuf> {
+    let path = PathBuf::from(value.as_str().ok_or_else(|| protocol("path missing"))?);
+    let plain = path.is_absolute() && path.components().all(|c| matches!(c, Component::RootDir | Component::Normal(_)));
+    if !plain || !path.starts_with(base) || (path == base && !dir) {
+        return Err(Error::PermissionDenied(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is outside {}", path.display(), base.display()),
+        )));
+    }
+    Ok(path)
+}
+
+// Also drops the file's manifest record and any directories it leaves empty
+fn remove(path: &Path, base: &Path) -> Result<()> {
+    fs::remove_file(path)?;
+    remove_empty_dirs(path.parent().unwrap_or(base), base);
+    manifest::forget(&manifest::manifest_path(Scope::System), &[path.to_path_buf()])?;
+    Ok(())
+}
+
+fn write(path: &Path, data: &[u8]) -> Result<()> {
+    fs::create_dir_all(path.parent().unwrap_or(Path::new("/")))?;
+    write_atomic(&mut &data[..], path)?;
+    set_permissions644(path)?;
+    Ok(())
+}
+
+/// Carries out the steps read from `input`, answering each on `output`,
+/// for files inside `base` only. This is `fontize helper`, run as root by an
+/// escalation tool; `base` must not come from anything the user controls.
+pub fn serve_helper(base: &Path, input: &mut impl BufRead, output: &mut impl Write) -> Result<()> {
+    // Whatever the invoking user's umask, installed fonts are world-readable
+    umask(Mode::from_bits_truncate(0o022));
+
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(());
+        }
+        let step: Value = serde_json::from_str(&header).map_err(protocol)?;
+        let result = match step["op"].as_str() {
+            Some("write") => {
+                let len = step["len"].as_u64().ok_or_else(|| protocol("len missing"))?;
+                let mut data = Vec::new();
+                input.take(len).read_to_end(&mut data)?;
+                if data.len() as u64 != len {
+                    return Err(protocol("truncated file data"));
+                }
+                target(&step["path"], base, false).and_then(|path| write(&path, &data)).map(|()| json!({ "ok": true }))
+            }
+            Some("record") => {
+                let entry = step["entry"].as_str().and_then(manifest::Entry::from_line)
+                    .ok_or_else(|| protocol("bad manifest entry"))?;
+                let destination = Value::from(entry.destination.to_string_lossy());
+                match (entry.scope, target(&destination, base, false)) {
+                    (Scope::System, Ok(_)) => manifest::record(&manifest::manifest_path(Scope::System), entry).map(|()| json!({ "ok": true })).map_err(Error::from),
+                    (_, Err(e)) => Err(e),
+                    (Scope::User, _) => Err(protocol("only system manifest entries are recorded")),
+                }
+            }
+            Some("refresh") => {
+                let dirs = step["dirs"].as_array().ok_or_else(|| protocol("dirs missing"))?
+                    .iter()
+                    .map(|dir| target(dir, base, true))
+                    .collect::<Result<Vec<_>>>();
+                dirs.and_then(|dirs| cache::refresh_font_cache(Scope::System, &dirs, false)).map(|refresh| json!({
+                    "ok": true,
+                    "fallback": refresh.fallback,
+                    "invoking_user": refresh.invoking_user,
+                }))
+            }
+            Some("remove") => {
+                target(&step["path"], base, false).and_then(|path| remove(&path, base)).map(|()| json!({ "ok": true }))
+            }
+            _ => return Err(protocol("unknown step")),
+        };
+        let answer = result.unwrap_or_else(|e| json!({ "ok": false, "error": e.to_string(), "category": e.category() }));
+        writeln!(output, "{answer}")?;
+        output.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::FontKind;
+
+    // Every answer `serve_helper` gives to `requests`, inside `base`
+    fn serve(base: &Path, requests: &[u8]) -> Result<Vec<Value>> {
+        let mut output = Vec::new();
+        serve_helper(base, &mut &requests[..], &mut output)?;
+        Ok(output.split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect())
+    }
+
+    fn write_request(path: &str, data: &[u8]) -> Vec<u8> {
+        let mut request = format!("{}\n", json!({ "op": "write", "path": path, "len": data.len() })).into_bytes();
+        request.extend_from_slice(data);
+        request
+    }
+
+    fn record_request(scope: Scope, destination: &str) -> Vec<u8> {
+        let entry = manifest::Entry {
+            installed_at: 0,
+            scope,
+            kind: FontKind::Ttf,
+            sha256: "00".repeat(32),
+            source: PathBuf::from("/tmp/a.ttf"),
+            destination: PathBuf::from(destination),
+        };
+        format!("{}\n", json!({ "op": "record", "entry": entry.to_line() })).into_bytes()
+    }
+
+    fn denied(answer: &Value) -> bool {
+        answer["ok"] == false && answer["category"] == "permission_denied"
+    }
+
+    fn base(name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!("fontize-helper-{}-{name}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    #[test]
+    fn writes_inside_the_base() {
+        let base = base("inside");
+        let path = base.join("TTF/A/A.ttf");
+        let answers = serve(&base, &write_request(path.to_str().unwrap(), b"font")).unwrap();
+        assert_eq!(answers[0]["ok"], true);
+        assert_eq!(fs::read(&path).unwrap(), b"font");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o644);
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn refuses_writes_outside_the_base() {
+        let base = base("outside");
+        let inside = base.join("TTF");
+        let escape = format!("{}/../escaped.ttf", inside.display());
+        let sibling = format!("{}-other/x.ttf", base.display());
+        for path in [escape.as_str(), sibling.as_str(), "/etc/passwd", "relative/x.ttf", base.to_str().unwrap()] {
+            let answers = serve(&base, &write_request(path, b"font")).unwrap();
+            assert!(denied(&answers[0]), "{path}: {}", answers[0]);
+        }
+        assert!(!base.parent().unwrap().join("escaped.ttf").exists());
+        assert!(!Path::new(&sibling).exists());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn answers_every_step_after_a_refusal() {
+        let base = base("batch");
+        let mut requests = write_request("/etc/x.ttf", b"font");
+        requests.extend(write_request(base.join("x.ttf").to_str().unwrap(), b"font"));
+        let answers = serve(&base, &requests).unwrap();
+        assert!(denied(&answers[0]));
+        assert_eq!(answers[1]["ok"], true);
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn refuses_foreign_manifest_records() {
+        let base = base("record");
+        let inside = base.join("x.ttf");
+        let answers = serve(&base, &record_request(Scope::User, inside.to_str().unwrap())).unwrap();
+        assert_eq!(answers[0]["ok"], false);
+        assert!(answers[0]["error"].as_str().unwrap().contains("only system manifest entries"));
+        let answers = serve(&base, &record_request(Scope::System, "/etc/x.ttf")).unwrap();
+        assert!(denied(&answers[0]));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn refuses_removals_and_refreshes_outside_the_base() {
+        let base = base("remove");
+        let victim = base.with_extension("victim");
+        fs::write(&victim, b"keep").unwrap();
+        let remove = |path: &Path| format!("{}\n", json!({ "op": "remove", "path": path }));
+        for path in [victim.as_path(), base.as_path(), &base.join("../x.ttf")] {
+            let answers = serve(&base, remove(path).as_bytes()).unwrap();
+            assert!(denied(&answers[0]), "{}: {}", path.display(), answers[0]);
+        }
+        assert!(victim.exists());
+        let refresh = format!("{}\n", json!({ "op": "refresh", "dirs": [base.parent().unwrap()] }));
+        assert!(denied(&serve(&base, refresh.as_bytes()).unwrap()[0]));
+        fs::remove_file(&victim).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn stops_on_malformed_requests() {
+        let base = base("malformed");
+        assert!(serve(&base, b"not json\n").is_err());
+        assert!(serve(&base, b"{\"op\":\"chmod\"}\n").is_err());
+        let mut truncated = write_request(base.join("x.ttf").to_str().unwrap(), b"font");
+        truncated.pop();
+        assert!(serve(&base, &truncated).is_err());
+        assert!(!base.join("x.ttf").exists());
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
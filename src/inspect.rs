@@ -0,0 +1,537 @@
+// Everything worth knowing about a font before installing it: the whole
+// naming table, OS/2 classification and embedding rights, coverage, glyph
+// count and variation axes. Legacy fonts only have the names fontize reads
+// to file them away.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::kind::{FontKind, detect_kind};
+use crate::sfnt::{self, Font, Names, read_u16, read_u32};
+use crate::{legacy, woff};
+
+#[derive(Debug, Clone)]
+pub struct NameRecord {
+    pub platform: u16,
+    pub encoding: u16,
+    /// BCP 47 tag where the language ID is known, else the raw ID in hex.
+    pub language: String,
+    pub name_id: u16,
+    pub value: String,
+}
+
+impl NameRecord {
+    /// What the record holds, as the OpenType spec calls it.
+    pub fn label(&self) -> String {
+        let label = match self.name_id {
+            0 => "Copyright",
+            1 => "Family",
+            2 => "Subfamily",
+            3 => "Unique ID",
+            4 => "Full name",
+            5 => "Version",
+            6 => "PostScript name",
+            7 => "Trademark",
+            8 => "Manufacturer",
+            9 => "Designer",
+            10 => "Description",
+            11 => "Vendor URL",
+            12 => "Designer URL",
+            13 => "License",
+            14 => "License URL",
+            16 => "Typographic family",
+            17 => "Typographic subfamily",
+            18 => "Compatible full name",
+            19 => "Sample text",
+            20 => "PostScript CID name",
+            21 => "WWS family",
+            22 => "WWS subfamily",
+            23 => "Light background palette",
+            24 => "Dark background palette",
+            25 => "Variations PostScript prefix",
+            id => return format!("Name {id}"),
+        };
+        label.to_string()
+    }
+}
+
+/// One axis of a variable font, in user-space units.
+#[derive(Debug, Clone)]
+pub struct Axis {
+    pub tag: String,
+    pub name: Option<String>,
+    pub min: f64,
+    pub default: f64,
+    pub max: f64,
+}
+
+/// What one face says about itself; collections have several.
+#[derive(Debug, Clone, Default)]
+pub struct FaceInfo {
+    pub names: Names,
+    pub records: Vec<NameRecord>,
+    pub designer: Option<String>,
+    pub manufacturer: Option<String>,
+    /// The four-letter vendor ID registered with Microsoft, from OS/2.
+    pub vendor_id: Option<String>,
+    pub license: Option<String>,
+    pub license_url: Option<String>,
+    pub weight: Option<u16>,
+    pub width: Option<u16>,
+    pub italic: bool,
+    /// Unicode blocks OS/2 claims coverage of.
+    pub unicode_ranges: Vec<&'static str>,
+    /// OpenType script tags with GSUB or GPOS lookups.
+    pub scripts: Vec<String>,
+    pub glyphs: Option<u16>,
+    pub axes: Vec<Axis>,
+    /// OS/2 embedding permission bits.
+    pub fs_type: Option<u16>,
+}
+
+impl FaceInfo {
+    pub fn weight_name(&self) -> Option<&'static str> {
+        Some(match self.weight? {
+            0..=149 => "Thin",
+            150..=249 => "ExtraLight",
+            250..=349 => "Light",
+            350..=449 => "Regular",
+            450..=549 => "Medium",
+            550..=649 => "SemiBold",
+            650..=749 => "Bold",
+            750..=849 => "ExtraBold",
+            _ => "Black",
+        })
+    }
+
+    pub fn width_name(&self) -> Option<&'static str> {
+        Some(match self.width? {
+            1 => "UltraCondensed",
+            2 => "ExtraCondensed",
+            3 => "Condensed",
+            4 => "SemiCondensed",
+            5 => "Normal",
+            6 => "SemiExpanded",
+            7 => "Expanded",
+            8 => "ExtraExpanded",
+            9 => "UltraExpanded",
+            _ => return None,
+        })
+    }
+
+    /// The embedding permissions fsType grants, most significant first.
+    pub fn embedding(&self) -> Vec<&'static str> {
+        let Some(fs_type) = self.fs_type else { return Vec::new() };
+        // When several usage bits are set, the least restrictive one applies
+        let mut rights = vec![match fs_type & 0xf {
+            0 => "installable",
+            usage if usage & 8 != 0 => "editable",
+            usage if usage & 4 != 0 => "preview & print",
+            _ => "restricted license",
+        }];
+        if fs_type & 0x100 != 0 {
+            rights.push("no subsetting");
+        }
+        if fs_type & 0x200 != 0 {
+            rights.push("bitmap embedding only");
+        }
+        rights
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FontInfo {
+    pub path: PathBuf,
+    pub kind: FontKind,
+    pub size: u64,
+    pub faces: Vec<FaceInfo>,
+}
+
+/// Reads everything [`FontInfo`] covers from a font file; web fonts are
+/// decoded first, and each face of a collection is inspected.
+pub fn inspect(path: &Path) -> Result<FontInfo> {
+    let kind = detect_kind(path)?;
+    let data = fs::read(path)?;
+    let size = data.len() as u64;
+    let faces = match kind {
+        FontKind::Pcf | FontKind::Bdf | FontKind::Type1 => {
+            vec![FaceInfo { names: legacy::read_names(path)?, ..FaceInfo::default() }]
+        }
+        FontKind::Collection => sfnt::collection_offsets(&data)?
+            .into_iter()
+            .map(|offset| Ok(face_info(&Font::parse_at(&data, offset as usize)?)))
+            .collect::<Result<_>>()?,
+        FontKind::Woff => vec![face_info(&Font::parse(&woff::decode_woff(&data)?)?)],
+        FontKind::Woff2 => vec![face_info(&Font::parse(&woff::decode_woff2(&data)?)?)],
+        FontKind::Otf | FontKind::Ttf => vec![face_info(&Font::parse(&data)?)],
+    };
+    Ok(FontInfo { path: path.to_path_buf(), kind, size, faces })
+}
+
+fn face_info(font: &Font) -> FaceInfo {
+    let records = name_records(font);
+    let mut info = FaceInfo {
+        names: font.names(),
+        designer: font.name(9),
+        manufacturer: font.name(8),
+        license: font.name(13),
+        license_url: font.name(14),
+        glyphs: font.table(b"maxp").and_then(|maxp| read_u16(maxp, 4)),
+        scripts: scripts(font),
+        axes: axes(font, &records),
+        records,
+        ..FaceInfo::default()
+    };
+    if let Some(os2) = font.table(b"OS/2") {
+        info.weight = read_u16(os2, 4);
+        info.width = read_u16(os2, 6);
+        info.fs_type = read_u16(os2, 8);
+        info.italic = read_u16(os2, 62).is_some_and(|selection| selection & 1 != 0);
+        info.vendor_id = os2.get(58..62)
+            .map(|id| String::from_utf8_lossy(id).trim_end_matches(['\0', ' ']).to_string())
+            .filter(|id| !id.is_empty());
+        for (word, offset) in [42, 46, 50, 54].into_iter().enumerate() {
+            let Some(bits) = read_u32(os2, offset) else { break };
+            for bit in 0..32 {
+                if bits & (1 << bit) != 0 {
+                    info.unicode_ranges.extend(UNICODE_RANGES.get(word * 32 + bit));
+                }
+            }
+        }
+    } else if let Some(head) = font.table(b"head") {
+        info.italic = read_u16(head, 44).is_some_and(|style| style & 2 != 0);
+    }
+    info
+}
+
+fn name_records(font: &Font) -> Vec<NameRecord> {
+    let Some(table) = font.table(b"name") else { return Vec::new() };
+    let (Some(version), Some(count), Some(storage)) = (read_u16(table, 0), read_u16(table, 2), read_u16(table, 4))
+    else {
+        return Vec::new();
+    };
+    let string = |platform: u16, length: u16, offset: u16| {
+        let start = storage as usize + offset as usize;
+        table.get(start..start + length as usize).map(|bytes| sfnt::decode_name(platform, bytes))
+    };
+
+    // Version 1 tables spell out languages 0x8000 and up as tags
+    let tags_at = 6 + count as usize * 12;
+    let tags: Vec<String> = match (version, read_u16(table, tags_at)) {
+        (1, Some(tag_count)) => (0..tag_count as usize)
+            .filter_map(|i| {
+                let rec = tags_at + 2 + i * 4;
+                string(0, read_u16(table, rec)?, read_u16(table, rec + 2)?)
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut records = Vec::new();
+    for i in 0..count as usize {
+        let rec = 6 + i * 12;
+        let (Some(platform), Some(encoding), Some(language), Some(name_id), Some(length), Some(offset)) = (
+            read_u16(table, rec),
+            read_u16(table, rec + 2),
+            read_u16(table, rec + 4),
+            read_u16(table, rec + 6),
+            read_u16(table, rec + 8),
+            read_u16(table, rec + 10),
+        ) else {
+            break;
+        };
+        let Some(value) = string(platform, length, offset) else { continue };
+        let language = match (platform, language) {
+            (0, _) => "und".to_string(),
+            (_, 0x8000..) => tags.get(language as usize - 0x8000).cloned().unwrap_or_else(|| format!("{language:#06x}")),
+            _ => language_tag(platform, language).map_or_else(|| format!("{language:#06x}"), str::to_string),
+        };
+        records.push(NameRecord { platform, encoding, language, name_id, value });
+    }
+    records
+}
+
+fn language_tag(platform: u16, language: u16) -> Option<&'static str> {
+    match platform {
+        1 => Some(match language {
+            0 => "en",
+            1 => "fr",
+            2 => "de",
+            3 => "it",
+            4 => "nl",
+            5 => "sv",
+            6 => "es",
+            7 => "da",
+            8 => "pt",
+            9 => "no",
+            10 => "he",
+            11 => "ja",
+            12 => "ar",
+            13 => "fi",
+            14 => "el",
+            19 => "zh-Hant",
+            23 => "ko",
+            32 => "ru",
+            33 => "zh-Hans",
+            _ => return None,
+        }),
+        3 => Some(match language {
+            0x0401 => "ar-SA",
+            0x0403 => "ca-ES",
+            0x0404 => "zh-TW",
+            0x0405 => "cs-CZ",
+            0x0406 => "da-DK",
+            0x0407 => "de-DE",
+            0x0408 => "el-GR",
+            0x0409 => "en-US",
+            0x040a | 0x0c0a => "es-ES",
+            0x040b => "fi-FI",
+            0x040c => "fr-FR",
+            0x040d => "he-IL",
+            0x040e => "hu-HU",
+            0x0410 => "it-IT",
+            0x0411 => "ja-JP",
+            0x0412 => "ko-KR",
+            0x0413 => "nl-NL",
+            0x0414 => "nb-NO",
+            0x0415 => "pl-PL",
+            0x0416 => "pt-BR",
+            0x0418 => "ro-RO",
+            0x0419 => "ru-RU",
+            0x041b => "sk-SK",
+            0x041d => "sv-SE",
+            0x041e => "th-TH",
+            0x041f => "tr-TR",
+            0x0421 => "id-ID",
+            0x0422 => "uk-UA",
+            0x0424 => "sl-SI",
+            0x0425 => "et-EE",
+            0x0426 => "lv-LV",
+            0x0427 => "lt-LT",
+            0x042a => "vi-VN",
+            0x042d => "eu-ES",
+            0x0804 => "zh-CN",
+            0x0809 => "en-GB",
+            0x080c => "fr-BE",
+            0x0816 => "pt-PT",
+            0x0c04 => "zh-HK",
+            0x0c0c => "fr-CA",
+            _ => return None,
+        }),
+        _ => None,
+    }
+}
+
+// Script tags from the script lists of GSUB and GPOS, each once
+fn scripts(font: &Font) -> Vec<String> {
+    let mut scripts = Vec::new();
+    for table in [b"GSUB", b"GPOS"].into_iter().filter_map(|tag| font.table(tag)) {
+        let Some(list) = read_u16(table, 4).map(usize::from) else { continue };
+        let Some(count) = read_u16(table, list) else { continue };
+        for i in 0..count as usize {
+            let Some(tag) = table.get(list + 2 + i * 6..list + 6 + i * 6) else { break };
+            let tag = String::from_utf8_lossy(tag).trim_end().to_string();
+            if !scripts.contains(&tag) {
+                scripts.push(tag);
+            }
+        }
+    }
+    scripts
+}
+
+fn axes(font: &Font, records: &[NameRecord]) -> Vec<Axis> {
+    let Some(fvar) = font.table(b"fvar") else { return Vec::new() };
+    let (Some(offset), Some(count), Some(size)) = (read_u16(fvar, 4), read_u16(fvar, 8), read_u16(fvar, 10)) else {
+        return Vec::new();
+    };
+    // 16.16 fixed point; three decimals is all the precision axis values mean
+    let fixed = |at: usize| read_u32(fvar, at).map(|v| (v as i32 as f64 / 65.536).round() / 1000.0);
+    (0..count as usize)
+        .map_while(|i| {
+            let rec = offset as usize + i * size as usize;
+            let name_id = read_u16(fvar, rec + 18)?;
+            Some(Axis {
+                tag: String::from_utf8_lossy(fvar.get(rec..rec + 4)?).into_owned(),
+                name: records.iter().find(|r| r.name_id == name_id).map(|r| r.value.clone()),
+                min: fixed(rec + 4)?,
+                default: fixed(rec + 8)?,
+                max: fixed(rec + 12)?,
+            })
+        })
+        .collect()
+}
+
+/// Human name of an OpenType script tag, for the common ones.
+pub fn script_name(tag: &str) -> Option<&'static str> {
+    Some(match tag {
+        "DFLT" => "Default",
+        "arab" => "Arabic",
+        "armn" => "Armenian",
+        "beng" | "bng2" => "Bengali",
+        "bopo" => "Bopomofo",
+        "brai" => "Braille",
+        "cans" => "Canadian Syllabics",
+        "cher" => "Cherokee",
+        "cyrl" => "Cyrillic",
+        "deva" | "dev2" => "Devanagari",
+        "ethi" => "Ethiopic",
+        "geor" => "Georgian",
+        "grek" => "Greek",
+        "gujr" | "gjr2" => "Gujarati",
+        "guru" | "gur2" => "Gurmukhi",
+        "hang" => "Hangul",
+        "hani" => "CJK Ideographic",
+        "hebr" => "Hebrew",
+        "kana" => "Hiragana and Katakana",
+        "khmr" => "Khmer",
+        "knda" | "knd2" => "Kannada",
+        "lao" => "Lao",
+        "latn" => "Latin",
+        "math" => "Mathematical Alphanumeric Symbols",
+        "mlym" | "mlm2" => "Malayalam",
+        "mong" => "Mongolian",
+        "mymr" | "mym2" => "Myanmar",
+        "nko" => "N'Ko",
+        "ogam" => "Ogham",
+        "orya" | "ory2" => "Odia",
+        "runr" => "Runic",
+        "sinh" => "Sinhala",
+        "syrc" => "Syriac",
+        "taml" | "tml2" => "Tamil",
+        "telu" | "tel2" => "Telugu",
+        "thaa" => "Thaana",
+        "thai" => "Thai",
+        "tibt" => "Tibetan",
+        "tfng" => "Tifinagh",
+        "yi" => "Yi",
+        _ => return None,
+    })
+}
+
+// OS/2 ulUnicodeRange bits, by bit number
+const UNICODE_RANGES: [&str; 123] = [
+    "Basic Latin",
+    "Latin-1 Supplement",
+    "Latin Extended-A",
+    "Latin Extended-B",
+    "IPA Extensions",
+    "Spacing Modifier Letters",
+    "Combining Diacritical Marks",
+    "Greek and Coptic",
+    "Coptic",
+    "Cyrillic",
+    "Armenian",
+    "Hebrew",
+    "Vai",
+    "Arabic",
+    "NKo",
+    "Devanagari",
+    "Bengali",
+    "Gurmukhi",
+    "Gujarati",
+    "Oriya",
+    "Tamil",
+    "Telugu",
+    "Kannada",
+    "Malayalam",
+    "Thai",
+    "Lao",
+    "Georgian",
+    "Balinese",
+    "Hangul Jamo",
+    "Latin Extended Additional",
+    "Greek Extended",
+    "General Punctuation",
+    "Superscripts And Subscripts",
+    "Currency Symbols",
+    "Combining Diacritical Marks For Symbols",
+    "Letterlike Symbols",
+    "Number Forms",
+    "Arrows",
+    "Mathematical Operators",
+    "Miscellaneous Technical",
+    "Control Pictures",
+    "Optical Character Recognition",
+    "Enclosed Alphanumerics",
+    "Box Drawing",
+    "Block Elements",
+    "Geometric Shapes",
+    "Miscellaneous Symbols",
+    "Dingbats",
+    "CJK Symbols And Punctuation",
+    "Hiragana",
+    "Katakana",
+    "Bopomofo",
+    "Hangul Compatibility Jamo",
+    "Phags-pa",
+    "Enclosed CJK Letters And Months",
+    "CJK Compatibility",
+    "Hangul Syllables",
+    "Non-Plane 0",
+    "Phoenician",
+    "CJK Unified Ideographs",
+    "Private Use Area (plane 0)",
+    "CJK Strokes",
+    "Alphabetic Presentation Forms",
+    "Arabic Presentation Forms-A",
+    "Combining Half Marks",
+    "Vertical Forms",
+    "Small Form Variants",
+    "Arabic Presentation Forms-B",
+    "Halfwidth And Fullwidth Forms",
+    "Specials",
+    "Tibetan",
+    "Syriac",
+    "Thaana",
+    "Sinhala",
+    "Myanmar",
+    "Ethiopic",
+    "Cherokee",
+    "Unified Canadian Aboriginal Syllabics",
+    "Ogham",
+    "Runic",
+    "Khmer",
+    "Mongolian",
+    "Braille Patterns",
+    "Yi Syllables",
+    "Tagalog",
+    "Old Italic",
+    "Gothic",
+    "Deseret",
+    "Byzantine Musical Symbols",
+    "Mathematical Alphanumeric Symbols",
+    "Private Use (plane 15)",
+    "Variation Selectors",
+    "Tags",
+    "Limbu",
+    "Tai Le",
+    "New Tai Lue",
+    "Buginese",
+    "Glagolitic",
+    "Tifinagh",
+    "Yijing Hexagram Symbols",
+    "Syloti Nagri",
+    "Linear B Syllabary",
+    "Ancient Greek Numbers",
+    "Ugaritic",
+    "Old Persian",
+    "Shavian",
+    "Osmanya",
+    "Cypriot Syllabary",
+    "Kharoshthi",
+    "Tai Xuan Jing Symbols",
+    "Cuneiform",
+    "Counting Rod Numerals",
+    "Sundanese",
+    "Lepcha",
+    "Ol Chiki",
+    "Saurashtra",
+    "Kayah Li",
+    "Rejang",
+    "Cham",
+    "Ancient Symbols",
+    "Phaistos Disc",
+    "Carian",
+    "Domino Tiles",
+];
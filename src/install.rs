@@ -0,0 +1,1030 @@
+// Installing and uninstalling fonts. Every font is planned first (format,
+// validation, names, destination, conflicts) before anything on disk is
+// touched, so a dry run and a real install report exactly the same decisions.
+// With a privileged helper configured, fonts bound for directories we cannot
+// write to are still planned here, and only their bytes are handed over.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::cache::{self, CacheRefresh, CacheStatus};
+use crate::error::{Error, Result};
+use crate::files::{
+    build_time, copy_atomic, files_under, is_numbered_copy, move_across_fs, needs_privileges, normalize_tree,
+    numbered_copies, path_component, remove_empty_dirs, set_permissions644, unique_path, write_atomic,
+};
+use crate::helper::{self, Step};
+use crate::kind::{has_font_extension, metric_files, read_names};
+use crate::layout::{Layout, Scope, scopes};
+use crate::sfnt::{self, Names};
+use crate::{FontKind, archive, detect_kind, legacy, manifest, validate, woff};
+
+// A font to install; `origin` is what the user knows it as: the file itself,
+// or `<archive>/<entry>` for fonts pulled out of a bundle.
+struct Source {
+    path: PathBuf,
+    origin: PathBuf,
+    input: usize,                                        // input path it came from
+    archive: Option<usize>,                              // index into the extracted archives
+}
+
+// A planned font whose files the privileged helper writes
+struct Delegated {
+    font: FontReport,
+    origin: PathBuf,
+    input: usize,
+    files: Range<usize>,                                 // its write steps
+    record: usize,                                       // its manifest step
+    consumed: Vec<PathBuf>,                              // sources to remove once written
+}
+
+// Destinations earlier fonts of a batch are bound for, with their checksum and names
+type Planned = HashMap<PathBuf, (String, Names)>;
+
+/// What to do when the destination already holds a different build of the same font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy { Skip, Replace, Rename, Fail }
+
+impl ConflictPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Replace => "replace",
+            ConflictPolicy::Rename => "rename",
+            ConflictPolicy::Fail => "fail",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ConflictPolicy> {
+        match name {
+            "skip" => Some(ConflictPolicy::Skip),
+            "replace" => Some(ConflictPolicy::Replace),
+            "rename" => Some(ConflictPolicy::Rename),
+            "fail" => Some(ConflictPolicy::Fail),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action { Installed, Replaced, Skipped }
+
+/// Everything decided about one font; after a dry run, what would happen.
+#[derive(Debug)]
+pub struct FontReport {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub kind: FontKind,
+    pub converted_from: Option<FontKind>,                // web font format it was unpacked from
+    pub names: Names,
+    pub faces: Vec<Names>,                               // every face, for collections
+    pub metrics: Vec<(PathBuf, PathBuf)>,                // Type 1 metric files, source and destination
+    pub sha256: String,
+    pub action: Action,
+    pub note: Option<&'static str>,
+    pub warnings: Vec<String>,                           // validation findings that didn't stop the install
+    converted: Option<Vec<u8>>,
+}
+
+/// A source that could not be installed.
+#[derive(Debug)]
+pub struct Failure {
+    pub source: PathBuf,
+    /// Index of the input path the source came from.
+    pub input: usize,
+    pub error: Error,
+}
+
+/// Progress of an install or plan, one font at a time.
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    Font(&'a FontReport),
+    Failure(&'a Failure),
+}
+
+#[derive(Debug)]
+pub struct InstallReport {
+    pub fonts: Vec<FontReport>,
+    /// License and readme files from archives, source name and destination.
+    pub docs: Vec<(PathBuf, PathBuf)>,
+    pub failures: Vec<Failure>,
+    /// Problems that did not make any font fail.
+    pub warnings: Vec<String>,
+    /// Whether some destination is not writable with the current rights.
+    pub needs_privileges: bool,
+    pub cache: CacheStatus,
+}
+
+impl InstallReport {
+    pub fn count(&self, action: Action) -> usize {
+        self.fonts.iter().filter(|font| font.action == action).count()
+    }
+}
+
+#[derive(Debug)]
+pub struct UninstallReport {
+    pub removed: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+    pub cache: CacheStatus,
+}
+
+/// Installs fonts into one scope:
+///
+/// ```no_run
+/// use fontize::{ConflictPolicy, Installer, Scope};
+///
+/// let report = Installer::new(Scope::User)
+///     .on_conflict(ConflictPolicy::Replace)
+///     .install(&["FiraCode.zip"])?;
+/// # Ok::<(), fontize::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Installer {
+    scope: Scope,
+    layout: Layout,
+    on_conflict: ConflictPolicy,
+    cache_refresh: CacheRefresh,
+    with_docs: bool,
+    move_files: bool,
+    decompress: bool,
+    split: bool,
+    force: bool,
+    helper: Option<Vec<OsString>>,
+}
+
+impl Installer {
+    pub fn new(scope: Scope) -> Installer {
+        Installer {
+            scope,
+            layout: Layout::default(),
+            on_conflict: ConflictPolicy::Fail,
+            cache_refresh: CacheRefresh::OnChange,
+            with_docs: false,
+            move_files: false,
+            decompress: true,
+            split: false,
+            force: false,
+            helper: None,
+        }
+    }
+
+    pub fn layout(mut self, layout: Layout) -> Installer {
+        self.layout = layout;
+        self
+    }
+
+    pub fn on_conflict(mut self, policy: ConflictPolicy) -> Installer {
+        self.on_conflict = policy;
+        self
+    }
+
+    pub fn cache_refresh(mut self, policy: CacheRefresh) -> Installer {
+        self.cache_refresh = policy;
+        self
+    }
+
+    /// Also install license/readme files found in archives.
+    pub fn with_docs(mut self, with_docs: bool) -> Installer {
+        self.with_docs = with_docs;
+        self
+    }
+
+    /// Move font files into place instead of copying them.
+    pub fn move_files(mut self, move_files: bool) -> Installer {
+        self.move_files = move_files;
+        self
+    }
+
+    /// Convert WOFF/WOFF2 files to TTF/OTF (the default) rather than
+    /// installing them as they are.
+    pub fn decompress(mut self, decompress: bool) -> Installer {
+        self.decompress = decompress;
+        self
+    }
+
+    /// Install each face of a TTC/OTC collection as its own font.
+    pub fn split(mut self, split: bool) -> Installer {
+        self.split = split;
+        self
+    }
+
+    /// Install fonts even if validation finds structural errors.
+    pub fn force(mut self, force: bool) -> Installer {
+        self.force = force;
+        self
+    }
+
+    /// Leave writing into, and removing from, directories that need more
+    /// rights to a helper: `command` runs `fontize helper` as root, e.g.
+    /// through sudo. Fonts are still read, checked and matched in this
+    /// process; the helper only gets bytes and paths.
+    pub fn privileged_helper(mut self, command: Vec<OsString>) -> Installer {
+        self.helper = Some(command);
+        self
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    /// The directory fonts are installed under.
+    pub fn base(&self) -> PathBuf {
+        self.layout.base(self.scope)
+    }
+
+    /// Installs fonts from files, directories (searched recursively) and
+    /// archives. Fonts that fail are listed in the report; only finding
+    /// nothing to install at all is an error.
+    pub fn install<P: AsRef<Path>>(&self, paths: &[P]) -> Result<InstallReport> {
+        self.run(paths, false, &mut |_| {})
+    }
+
+    /// Like [`Installer::install`], also passing every font and failure to
+    /// `progress` as soon as it is done.
+    pub fn install_with<P: AsRef<Path>>(&self, paths: &[P], mut progress: impl FnMut(Event)) -> Result<InstallReport> {
+        self.run(paths, false, &mut progress)
+    }
+
+    /// Works out what [`Installer::install`] would do, without changing anything.
+    pub fn plan<P: AsRef<Path>>(&self, paths: &[P]) -> Result<InstallReport> {
+        self.run(paths, true, &mut |_| {})
+    }
+
+    pub fn plan_with<P: AsRef<Path>>(&self, paths: &[P], mut progress: impl FnMut(Event)) -> Result<InstallReport> {
+        self.run(paths, true, &mut progress)
+    }
+
+    fn run<P: AsRef<Path>>(&self, paths: &[P], dry_run: bool, progress: &mut dyn FnMut(Event)) -> Result<InstallReport> {
+        let mut report = InstallReport {
+            fonts: Vec::new(),
+            docs: Vec::new(),
+            failures: Vec::new(),
+            warnings: Vec::new(),
+            needs_privileges: false,
+            cache: CacheStatus::Skipped,
+        };
+        let mut archives = Vec::new();
+        let sources = self.collect_sources(paths, &mut report, &mut archives);
+        if sources.is_empty() && report.failures.is_empty() {
+            return Err(Error::NotFound("No fonts found to install".into()));
+        }
+        for failure in &report.failures {
+            progress(Event::Failure(failure));
+        }
+
+        let mut doc_dirs = vec![Vec::new(); archives.len()];
+        let mut steps = Vec::new();
+        let mut delegated = Vec::new();
+        let mut planned = Planned::new();
+        for src in &sources {
+            let result = self.plan_install(src, &planned).and_then(|font| {
+                if font.action != Action::Skipped {
+                    planned.insert(font.destination.clone(), (font.sha256.clone(), font.names.clone()));
+                }
+                if dry_run {
+                    Ok(Some(font))
+                } else if self.delegates(&font.destination) && font.action != Action::Skipped {
+                    delegated.push(self.delegation(src, font, &mut steps)?);
+                    Ok(None)
+                } else {
+                    self.do_install(src, font).map(Some)
+                }
+            });
+            match result {
+                Ok(Some(font)) => {
+                    if let (Some(i), true) = (src.archive, font.action != Action::Skipped) {
+                        push_dir(&mut doc_dirs[i], &font.destination);
+                    }
+                    progress(Event::Font(&font));
+                    report.fonts.push(font);
+                }
+                Ok(None) => {
+                    // Its docs go to the helper as well, on the chance it succeeds
+                    if let (Some(i), Some(d)) = (src.archive, delegated.last()) {
+                        push_dir(&mut doc_dirs[i], &d.font.destination);
+                    }
+                }
+                Err(error) => {
+                    let failure = Failure { source: src.origin.clone(), input: src.input, error };
+                    progress(Event::Failure(&failure));
+                    report.failures.push(failure);
+                }
+            }
+        }
+        let mut doc_steps = Vec::new();
+        for (extracted, dirs) in archives.iter().zip(&doc_dirs) {
+            for (doc, dest) in doc_targets(extracted, dirs) {
+                let name = PathBuf::from(doc.file_name().unwrap_or_default());
+                if !dry_run && self.delegates(&dest) {
+                    match fs::read(&doc) {
+                        Ok(data) => {
+                            doc_steps.push((steps.len(), name, dest.clone()));
+                            steps.push(Step::Write { path: dest, data });
+                        }
+                        Err(e) => report.warnings.push(format!("could not install {}: {e}", dest.display())),
+                    }
+                    continue;
+                }
+                if !dry_run && let Err(e) = fs::copy(&doc, &dest).and_then(|_| set_permissions644(&dest)) {
+                    report.warnings.push(format!("could not install {}: {e}", dest.display()));
+                    continue;
+                }
+                report.docs.push((name, dest));
+            }
+        }
+
+        // Before the cache is built: fontconfig checks it against directory mtimes
+        if let (Some(root), false) = (&self.layout.root, dry_run) {
+            let mut written: Vec<PathBuf> = report.fonts.iter()
+                .filter(|font| font.action != Action::Skipped)
+                .flat_map(|font| {
+                    let metrics = font.metrics.iter().map(|(_, dest)| dest.clone());
+                    std::iter::once(font.destination.clone()).chain(metrics)
+                })
+                .chain(report.docs.iter().map(|(_, dest)| dest.clone()))
+                .collect();
+            written.push(self.layout.manifest(self.scope));
+            if let Err(e) = normalize_tree(root, &written) {
+                report.warnings.push(format!("could not normalize the staged files: {e}"));
+            }
+        }
+
+        let mut changed_dirs: Vec<PathBuf> = report.fonts.iter()
+            .chain(delegated.iter().map(|d| &d.font))
+            .filter(|font| font.action != Action::Skipped)
+            .filter_map(|font| font.destination.parent().map(Path::to_path_buf))
+            .collect();
+        report.needs_privileges = changed_dirs.iter().any(|dir| needs_privileges(dir));
+        let refresh_cache = self.cache_refresh.wanted(!changed_dirs.is_empty());
+        if !steps.is_empty() {
+            // The helper refreshes the system cache in the same run, so one
+            // authentication covers everything
+            let refresh_step = refresh_cache.then(|| {
+                steps.push(Step::Refresh(changed_dirs.clone()));
+                steps.len() - 1
+            });
+            let started = Instant::now();
+            let mut results: Vec<Option<Result<Value>>> = match helper::delegate(self.helper.as_deref().unwrap_or_default(), &steps) {
+                Ok(results) => results.into_iter().map(Some).collect(),
+                Err(e) => {
+                    // Nothing got written; every step fails alike
+                    let message = e.to_string();
+                    steps.iter().map(|_| Some(Err(Error::Escalation(message.clone())))).collect()
+                }
+            };
+            let mut answer = |step: usize| results[step].take().unwrap_or(Ok(Value::Null));
+            for mut d in delegated {
+                let written: Result<Vec<Value>> = d.files.clone().map(&mut answer).collect();
+                let recorded = answer(d.record);
+                if let Err(error) = written {
+                    changed_dirs.retain(|dir| Some(dir.as_path()) != d.font.destination.parent());
+                    let failure = Failure { source: d.origin, input: d.input, error };
+                    progress(Event::Failure(&failure));
+                    report.failures.push(failure);
+                    continue;
+                }
+                if let Err(e) = recorded {
+                    d.font.warnings.push(format!("could not update manifest: {e}"));
+                }
+                for consumed in &d.consumed {
+                    if let Err(e) = fs::remove_file(consumed) {
+                        d.font.warnings.push(format!("could not remove {}: {e}", consumed.display()));
+                    }
+                }
+                progress(Event::Font(&d.font));
+                report.fonts.push(d.font);
+            }
+            for (step, name, dest) in doc_steps {
+                match answer(step) {
+                    Ok(_) => report.docs.push((name, dest)),
+                    Err(e) => report.warnings.push(format!("could not install {}: {e}", dest.display())),
+                }
+            }
+            if let Some(step) = refresh_step {
+                report.cache = match answer(step) {
+                    Ok(done) => CacheStatus::Refreshed(cache::Refresh {
+                        scope: Scope::System,
+                        dirs: cache::refresh_dirs(&changed_dirs),
+                        forced: false,
+                        fallback: done["fallback"].as_bool().unwrap_or(false),
+                        invoking_user: done["invoking_user"].as_str().map(String::from),
+                        elapsed: started.elapsed(),
+                    }),
+                    Err(e) => CacheStatus::Failed(e),
+                };
+            }
+        } else if refresh_cache {
+            report.cache = if dry_run {
+                // Planned directories may not exist yet; they are what gets refreshed
+                CacheStatus::Pending(cache::outermost_dirs(changed_dirs))
+            } else {
+                self.refresh(&[(self.scope, changed_dirs)])
+            };
+        }
+        Ok(report)
+    }
+
+    fn push_source(
+        &self,
+        path: PathBuf,
+        input: usize,
+        sources: &mut Vec<Source>,
+        archives: &mut Vec<archive::Extracted>,
+        warnings: &mut Vec<String>,
+    ) -> Result<()> {
+        let kind = detect_kind(&path);
+        let split = self.split && matches!(kind, Ok(FontKind::Collection));
+        if !split && (kind.is_ok() || !archive::is_archive(&path)) {
+            sources.push(Source { origin: path.clone(), path, input, archive: None });
+            return Ok(());
+        }
+        let extracted = if split {
+            archive::split_collection(&path)?
+        } else {
+            archive::extract(&path, self.with_docs)?
+        };
+        if extracted.fonts.is_empty() {
+            warnings.push(format!("no fonts found in {}", path.display()));
+        }
+        for (font, origin) in &extracted.fonts {
+            sources.push(Source { path: font.clone(), origin: origin.clone(), input, archive: Some(archives.len()) });
+        }
+        archives.push(extracted);
+        Ok(())
+    }
+
+    // Expands the inputs into font files: plain files are taken as given,
+    // archives are unpacked, and directories are walked recursively for
+    // anything that sniffs as a font or an archive.
+    fn collect_sources<P: AsRef<Path>>(
+        &self,
+        paths: &[P],
+        report: &mut InstallReport,
+        archives: &mut Vec<archive::Extracted>,
+    ) -> Vec<Source> {
+        let mut sources = Vec::new();
+        let mut failed = |source: PathBuf, input: usize, error: Error| {
+            report.failures.push(Failure { source, input, error });
+        };
+        for (input, path) in paths.iter().map(|p| p.as_ref().to_path_buf()).enumerate() {
+            if path.is_dir() {
+                let mut files = Vec::new();
+                if let Err(e) = files_under(&path, &mut files) {
+                    failed(path, input, e.into());
+                    continue;
+                }
+                files.sort();
+                for file in files {
+                    if detect_kind(&file).is_err() && !archive::is_archive(&file) {
+                        continue;
+                    }
+                    if let Err(e) = self.push_source(file.clone(), input, &mut sources, archives, &mut report.warnings) {
+                        failed(file, input, e);
+                    }
+                }
+            } else if path.is_file() {
+                if let Err(e) = self.push_source(path.clone(), input, &mut sources, archives, &mut report.warnings) {
+                    failed(path, input, e);
+                }
+            } else {
+                let e = Error::NotFound("source does not exist or is not a file or directory".into());
+                failed(path, input, e);
+            }
+        }
+        sources
+    }
+
+    fn plan_install(&self, src: &Source, planned: &Planned) -> Result<FontReport> {
+        let src_path = src.path.as_path();
+        let mut kind = detect_kind(src_path)?;
+        let file_name = src_path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid source filename"))?;
+
+        let mut data = fs::read(src_path)?;
+        // Web fonts are unpacked to the sfnt they wrap; the result is what gets
+        // installed unless they are to be kept as they are
+        let mut from = None;
+        let mut converted = None;
+        let sfnt_data = match kind {
+            FontKind::Woff | FontKind::Woff2 => {
+                let sfnt = if kind == FontKind::Woff { woff::decode_woff(&data)? } else { woff::decode_woff2(&data)? };
+                if self.decompress {
+                    from = Some(kind);
+                    kind = if sfnt.starts_with(b"OTTO") { FontKind::Otf } else { FontKind::Ttf };
+                    data = sfnt;
+                    converted = Some(data.clone());
+                    None
+                } else {
+                    Some(sfnt)
+                }
+            }
+            _ => None,
+        };
+        let sfnt_data = sfnt_data.as_deref().unwrap_or(&data);
+
+        // Legacy formats carry their names outside any name table; failing to
+        // find them means the file is damaged
+        let legacy_names = match kind {
+            FontKind::Pcf => Some(legacy::gunzip(&data).and_then(|d| legacy::pcf_names(&d))),
+            FontKind::Bdf => Some(legacy::gunzip(&data).and_then(|d| legacy::bdf_names(&d))),
+            FontKind::Type1 => Some(legacy::type1_names(&data)),
+            _ => None,
+        };
+
+        let mut warnings = Vec::new();
+        let mut errors = Vec::new();
+        let diagnostics = match &legacy_names {
+            Some(Err(e)) => vec![validate::Diagnostic { severity: validate::Severity::Error, message: e.to_string() }],
+            Some(Ok(_)) => Vec::new(),
+            None => validate::check(sfnt_data),
+        };
+        for d in diagnostics {
+            match d.severity {
+                validate::Severity::Error => errors.push(d.message),
+                validate::Severity::Warning => warnings.push(d.message),
+            }
+        }
+        if !errors.is_empty() {
+            if !self.force {
+                return Err(Error::Validation(errors));
+            }
+            warnings.append(&mut errors);
+        }
+
+        // Lay out as <subdir>/<Family>/<PostScriptName>.<ext>, falling back to the
+        // source file name for whatever the name table doesn't provide
+        let faces = if kind == FontKind::Collection { sfnt::collection_faces(sfnt_data)? } else { Vec::new() };
+        let names = match legacy_names {
+            Some(names) => names.unwrap_or_default(),
+            None => match sfnt::Font::parse(sfnt_data) {
+                Ok(font) => font.names(),
+                Err(e) => {
+                    warnings.push(format!("could not read names: {e}"));
+                    Names::default()
+                }
+            },
+        };
+        let dest_dir = self.layout.dest_dir(self.scope, kind, names.family.as_deref());
+        let ext = match src_path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()) {
+            Some(ext) if has_font_extension(src_path) && from.is_none() => ext,
+            _ if kind == FontKind::Type1 => if data.starts_with(&[0x80]) { "pfb" } else { "pfa" }.to_string(),
+            _ => kind.name().to_string(),
+        };
+        // A collection holds several faces, so no single PostScript name fits it
+        let face_name = names.postscript.clone().or_else(|| {
+            let family = names.family.as_deref()?.replace(' ', "");
+            let subfamily = names.subfamily.as_deref()?.replace(' ', "");
+            Some(format!("{family}-{subfamily}"))
+        });
+        // Bitmap fonts come in one file per size, and their file names say which
+        let keeps_file_name = matches!(kind, FontKind::Collection | FontKind::Pcf | FontKind::Bdf);
+        let dest_name = match face_name.as_deref().and_then(path_component) {
+            Some(face) if !keeps_file_name => PathBuf::from(format!("{face}.{ext}")),
+            _ if from.is_some() => Path::new(file_name).with_extension(ext),
+            _ => PathBuf::from(file_name),
+        };
+
+        let mut font = FontReport {
+            source: src.origin.clone(),
+            destination: dest_dir.join(dest_name),
+            kind,
+            converted_from: from,
+            sha256: manifest::sha256_bytes(&data),
+            names,
+            faces,
+            metrics: Vec::new(),
+            action: Action::Installed,
+            note: None,
+            warnings,
+            converted,
+        };
+        // Fonts planned earlier in the batch count as installed already, so
+        // a plan comes to the decisions the install it predicts will
+        let taken = |path: &Path| planned.contains_key(path) || path.exists();
+        let sha256 = |path: &Path| -> Result<String> {
+            match planned.get(path) {
+                Some((sha256, _)) => Ok(sha256.clone()),
+                None => Ok(manifest::sha256_file(path)?),
+            }
+        };
+        // The same bytes are never a conflict, just nothing to do, whether
+        // they sit at the destination or in a copy renamed on an earlier run
+        let mut installed = numbered_copies(&font.destination);
+        installed.extend(planned.keys().filter(|path| is_numbered_copy(&font.destination, path)).cloned());
+        installed.sort();
+        installed.dedup();
+        if taken(&font.destination) {
+            installed.insert(0, font.destination.clone());
+        }
+        for path in installed {
+            if sha256(&path)? == font.sha256 {
+                font.note = Some(if planned.contains_key(&path) {
+                    "identical to a font earlier in the batch"
+                } else {
+                    "identical file already installed"
+                });
+                font.destination = path;
+                font.action = Action::Skipped;
+                return Ok(font);
+            }
+        }
+        if taken(&font.destination) {
+            let existing = match planned.get(&font.destination) {
+                Some((_, names)) => names.clone(),
+                None => read_names(&font.destination).unwrap_or_default(),
+            };
+            let same_font = match (&existing.postscript, &font.names.postscript) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            };
+            if !same_font {
+                // Only the file names collide; keep both fonts
+                font.destination = unique_path(font.destination, taken);
+            } else {
+                match self.on_conflict {
+                    ConflictPolicy::Skip => {
+                        font.action = Action::Skipped;
+                        font.note = Some("keeping the installed build");
+                        return Ok(font);
+                    }
+                    // Never a downgrade: a newer installed build stays
+                    ConflictPolicy::Replace if is_newer(existing.version.as_deref(), font.names.version.as_deref()) => {
+                        font.action = Action::Skipped;
+                        font.note = Some("installed build is newer");
+                        return Ok(font);
+                    }
+                    ConflictPolicy::Replace => font.action = Action::Replaced,
+                    ConflictPolicy::Rename => font.destination = unique_path(font.destination, taken),
+                    ConflictPolicy::Fail => {
+                        return Err(Error::Conflict {
+                            destination: font.destination,
+                            installed: existing.postscript,
+                            installed_version: existing.version,
+                            version: font.names.version,
+                        });
+                    }
+                }
+            }
+        }
+
+        if kind == FontKind::Type1 {
+            font.metrics = metric_files(src_path)
+                .into_iter()
+                .map(|metrics| {
+                    let ext = metrics.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+                    let dest = font.destination.with_extension(ext);
+                    (metrics, dest)
+                })
+                .collect();
+        }
+        Ok(font)
+    }
+
+    fn do_install(&self, src: &Source, mut font: FontReport) -> Result<FontReport> {
+        if font.action == Action::Skipped {
+            return Ok(font);
+        }
+        let src_path = src.path.as_path();
+        let dest_path = font.destination.as_path();
+        let source = self.recorded_source(src)?;
+        let dest_dir = dest_path.parent().unwrap_or(Path::new("/"));
+
+        fs::create_dir_all(dest_dir)?;                   // may hit EACCES
+        // Files extracted from archives are ours to consume; user files are only
+        // taken away when asked to
+        let consume = self.move_files || src.archive.is_some();
+        if let Some(bytes) = font.converted.take() {
+            write_atomic(&mut bytes.as_slice(), dest_path)?;  // may hit EACCES
+            if consume {
+                fs::remove_file(src_path)?;
+            }
+        } else if consume {
+            move_across_fs(src_path, dest_path)?;        // may hit EACCES
+        } else {
+            copy_atomic(src_path, dest_path)?;           // may hit EACCES
+        }
+        set_permissions644(dest_path)?;                  // may hit EACCES
+        for (metrics, dest) in &font.metrics {
+            if consume {
+                move_across_fs(metrics, dest)?;
+            } else {
+                copy_atomic(metrics, dest)?;
+            }
+            set_permissions644(dest)?;
+        }
+
+        let entry = manifest::Entry {
+            installed_at: self.timestamp(),
+            scope: self.scope,
+            kind: font.kind,
+            sha256: font.sha256.clone(),
+            source,
+            destination: self.layout.deployed(&font.destination),
+        };
+        if let Err(e) = manifest::record(&self.layout.manifest(self.scope), entry) {
+            font.warnings.push(format!("could not update manifest: {e}"));
+        }
+        Ok(font)
+    }
+
+    // Staged manifests name just the source file: where it sat on the build
+    // host is neither reproducible nor anything the installed system knows
+    fn recorded_source(&self, src: &Source) -> io::Result<PathBuf> {
+        match self.layout.root {
+            Some(_) => Ok(src.origin.file_name().map(PathBuf::from).unwrap_or_default()),
+            None => std::path::absolute(&src.origin),
+        }
+    }
+
+    // Staged installs are stamped with the build's time, not the clock's
+    fn timestamp(&self) -> u64 {
+        match self.layout.root {
+            Some(_) => build_time().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            None => manifest::now(),
+        }
+    }
+
+    // One fc-cache run per scope that changed; staged trees get theirs built
+    // inside them, never the host's
+    fn refresh(&self, changes: &[(Scope, Vec<PathBuf>)]) -> CacheStatus {
+        let mut merged: Option<cache::Refresh> = None;
+        for scope in [Scope::User, Scope::System] {
+            let dirs: Vec<PathBuf> = changes.iter()
+                .filter(|(changed, _)| *changed == scope)
+                .flat_map(|(_, dirs)| dirs.iter().cloned())
+                .collect();
+            if !changes.iter().any(|(changed, _)| *changed == scope) {
+                continue;
+            }
+            let refreshed = match &self.layout.root {
+                Some(root) => cache::refresh_staged_cache(root, scope, &dirs, false),
+                None => cache::refresh_font_cache(scope, &dirs, false),
+            };
+            match refreshed {
+                Ok(refresh) => match &mut merged {
+                    Some(merged) => merged.merge(refresh),
+                    None => merged = Some(refresh),
+                },
+                Err(e) => return CacheStatus::Failed(e),
+            }
+        }
+        merged.map_or(CacheStatus::Skipped, CacheStatus::Refreshed)
+    }
+
+    // Whether writing at `path` is left to the privileged helper
+    fn delegates(&self, path: &Path) -> bool {
+        self.helper.is_some() && needs_privileges(path.parent().unwrap_or(Path::new("/")))
+    }
+
+    // What do_install would do, as steps for the helper: the font's bytes
+    // (already unpacked, for web fonts), its metric files and its manifest line
+    fn delegation(&self, src: &Source, mut font: FontReport, steps: &mut Vec<Step>) -> Result<Delegated> {
+        let start = steps.len();
+        let data = match font.converted.take() {
+            Some(bytes) => bytes,
+            None => fs::read(&src.path)?,
+        };
+        steps.push(Step::Write { path: font.destination.clone(), data });
+        for (metrics, dest) in &font.metrics {
+            steps.push(Step::Write { path: dest.clone(), data: fs::read(metrics)? });
+        }
+        let files = start..steps.len();
+        steps.push(Step::Record(manifest::Entry {
+            installed_at: self.timestamp(),
+            scope: self.scope,
+            kind: font.kind,
+            sha256: font.sha256.clone(),
+            source: self.recorded_source(src)?,
+            destination: font.destination.clone(),
+        }));
+        // Extracted files go away with their temporary directory anyway
+        let consumed = if self.move_files && src.archive.is_none() {
+            std::iter::once(src.path.clone()).chain(font.metrics.iter().map(|(metrics, _)| metrics.clone())).collect()
+        } else {
+            Vec::new()
+        };
+        Ok(Delegated { origin: src.origin.clone(), input: src.input, font, files, record: steps.len() - 1, consumed })
+    }
+
+    /// Removes installed fonts matching `query`: a path, a file name or stem,
+    /// or a family name. System scope also looks at the user's fonts. Fonts
+    /// are matched here; with a privileged helper, only removing the ones we
+    /// may not is left to it.
+    pub fn uninstall(&self, query: &str) -> Result<UninstallReport> {
+        let bases: Vec<PathBuf> = scopes(self.scope).into_iter().map(|scope| self.layout.base(scope)).collect();
+        let target = query_path(query, &bases);
+
+        let mut report = UninstallReport { removed: Vec::new(), warnings: Vec::new(), cache: CacheStatus::Skipped };
+        let mut changes = Vec::new();
+        let mut steps = Vec::new();
+        let mut delegated_dirs = Vec::new();
+        for scope in scopes(self.scope) {
+            let base = &self.layout.base(scope);
+            let mut files = Vec::new();
+            let mut forgotten = Vec::new();
+            files_under(base, &mut files)?;
+            for file in files.into_iter().filter(|f| has_font_extension(f)) {
+                let hit = match &target {
+                    Some(target) => fs::canonicalize(&file).ok().as_ref() == Some(target),
+                    None => matches_font(&file, query),
+                };
+                if !hit {
+                    continue;
+                }
+                // Type 1 metrics are useless without the outlines
+                let ext = file.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+                let metrics = if matches!(ext.as_deref(), Some("pfb" | "pfa")) { metric_files(&file) } else { Vec::new() };
+                if scope == Scope::System && self.delegates(&file) {
+                    // The helper forgets them in the system manifest too
+                    steps.extend(std::iter::once(file.clone()).chain(metrics).map(Step::Remove));
+                    push_dir(&mut delegated_dirs, &file);
+                    continue;
+                }
+                fs::remove_file(&file)?;                 // may hit EACCES
+                report.removed.push(file.clone());
+                for metrics in metrics {
+                    fs::remove_file(&metrics)?;
+                    report.removed.push(metrics);
+                }
+                if let Some(parent) = file.parent() {
+                    remove_empty_dirs(parent, base);
+                    changes.push((scope, vec![parent.to_path_buf()]));
+                }
+                forgotten.push(file);
+            }
+            let deployed: Vec<PathBuf> = forgotten.iter().map(|file| self.layout.deployed(file)).collect();
+            if let Err(e) = manifest::forget(&self.layout.manifest(scope), &deployed) {
+                report.warnings.push(format!("could not update manifest: {e}"));
+            }
+            if let Some(root) = &self.layout.root {
+                forgotten.push(self.layout.manifest(scope));
+                if let Err(e) = normalize_tree(root, &forgotten) {
+                    report.warnings.push(format!("could not normalize the staged files: {e}"));
+                }
+            }
+        }
+
+        let mut helper_cache = None;
+        if !steps.is_empty() {
+            let removals = steps.len();
+            let refresh_step = self.cache_refresh.wanted(true).then(|| {
+                steps.push(Step::Refresh(delegated_dirs.clone()));
+                steps.len() - 1
+            });
+            let started = Instant::now();
+            let mut results = match helper::delegate(self.helper.as_deref().unwrap_or_default(), &steps) {
+                Ok(results) => results,
+                Err(e) => {
+                    let message = e.to_string();
+                    steps.iter().map(|_| Err(Error::Escalation(message.clone()))).collect()
+                }
+            };
+            let mut first_error = None;
+            for (step, result) in steps.iter().zip(&mut results).take(removals) {
+                let Step::Remove(path) = step else { continue };
+                match std::mem::replace(result, Ok(Value::Null)) {
+                    Ok(_) => report.removed.push(path.clone()),
+                    Err(e) => {
+                        report.warnings.push(format!("could not remove {}: {e}", path.display()));
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            // Nothing removed at all is the error itself, as it is without a helper
+            if let (true, Some(e)) = (report.removed.is_empty(), first_error) {
+                return Err(e);
+            }
+            helper_cache = refresh_step.map(|step| match std::mem::replace(&mut results[step], Ok(Value::Null)) {
+                Ok(done) => CacheStatus::Refreshed(cache::Refresh {
+                    scope: Scope::System,
+                    dirs: cache::refresh_dirs(&delegated_dirs),
+                    forced: false,
+                    fallback: done["fallback"].as_bool().unwrap_or(false),
+                    invoking_user: done["invoking_user"].as_str().map(String::from),
+                    elapsed: started.elapsed(),
+                }),
+                Err(e) => CacheStatus::Failed(e),
+            });
+        }
+
+        if report.removed.is_empty() {
+            return Err(Error::NotFound(format!("No installed font matches {query}")));
+        }
+        if self.cache_refresh.wanted(true) {
+            report.cache = match (helper_cache, changes.is_empty()) {
+                (Some(cache), true) => cache,
+                (Some(CacheStatus::Refreshed(helper)), false) => match self.refresh(&changes) {
+                    CacheStatus::Refreshed(mut refresh) => {
+                        refresh.merge(helper);
+                        CacheStatus::Refreshed(refresh)
+                    }
+                    other => other,
+                },
+                (Some(failed), false) => failed,
+                (None, _) => self.refresh(&changes),
+            };
+        }
+        Ok(report)
+    }
+}
+
+// License and readme files go next to the fonts their archive provided,
+// prefixed with the archive name so bundles sharing a directory don't clash.
+fn doc_targets(extracted: &archive::Extracted, dest_dirs: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+    let mut targets = Vec::new();
+    for dest_dir in dest_dirs {
+        for doc in &extracted.docs {
+            let Some(file_name) = doc.file_name() else { continue };
+            let dest = dest_dir.join(format!("{}-{}", extracted.name, file_name.to_string_lossy()));
+            targets.push((doc.clone(), dest));
+        }
+    }
+    targets
+}
+
+fn push_dir(dirs: &mut Vec<PathBuf>, dest: &Path) {
+    if let Some(dir) = dest.parent()
+        && !dirs.iter().any(|d| d == dir)
+    {
+        dirs.push(dir.to_path_buf());
+    }
+}
+
+// Whether the `installed` version string names a later release than
+// `candidate`; unreadable versions never count as newer
+fn is_newer(installed: Option<&str>, candidate: Option<&str>) -> bool {
+    match (installed.and_then(version_number), candidate.and_then(version_number)) {
+        (Some(installed), Some(candidate)) => installed > candidate,
+        _ => false,
+    }
+}
+
+// The decimal a name ID 5 string starts with ("Version 2.010; ttfautohint"),
+// as the whole part and the fraction's digits without trailing zeros, which
+// then compare like the decimals: 2.1 > 2.01 == 2.010
+fn version_number(version: &str) -> Option<(u64, String)> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let number = &version[version.find(|c: char| c.is_ascii_digit())?..];
+    let (whole, rest) = number.split_at(digits(number));
+    let fraction = rest.strip_prefix('.').map_or("", |rest| &rest[..digits(rest)]);
+    Some((whole.parse().ok()?, fraction.trim_end_matches('0').to_string()))
+}
+
+pub(crate) fn normalize_name(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The file `query` names by location, for commands that also take names:
+/// a query is a path when it contains a `/`, or resolves to a file under one
+/// of `bases`. A bare file name that merely exists in the current directory
+/// is matched by name like any other.
+pub fn query_path(query: &str, bases: &[PathBuf]) -> Option<PathBuf> {
+    let path = fs::canonicalize(query).ok()?;
+    let under_base = || bases.iter().any(|base| fs::canonicalize(base).is_ok_and(|base| path.starts_with(base)));
+    (query.contains('/') || under_base()).then_some(path)
+}
+
+/// A query matches by full file name, by stem, or by family: either the part
+/// of the stem before the style suffix ("Fira Code" matches FiraCode-Bold.ttf)
+/// or the family recorded in the font itself.
+pub fn matches_font(path: &Path, query: &str) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let stem = path.file_stem().and_then(|n| n.to_str()).unwrap_or("");
+    if name.eq_ignore_ascii_case(query) || stem.eq_ignore_ascii_case(query) {
+        return true;
+    }
+    let family = stem.split(['-', '_']).next().unwrap_or(stem);
+    let query = normalize_name(query);
+    if query.is_empty() {
+        return false;
+    }
+    normalize_name(family) == query
+        || read_names(path).ok()
+            .and_then(|names| names.family)
+            .is_some_and(|family| normalize_name(&family) == query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions_as_decimals() {
+        assert!(is_newer(Some("Version 2.1"), Some("Version 2.010")));
+        assert!(is_newer(Some("Version 3.000; ttfautohint (v1.8)"), Some("Version 2.101")));
+        assert!(!is_newer(Some("Version 2.01"), Some("Version 2.010")));
+        assert!(!is_newer(Some("Version 1.9"), Some("Version 1.95")));
+        assert!(!is_newer(Some("2.0"), None));
+        assert!(!is_newer(Some("unknown"), Some("Version 1.0")));
+    }
+}
@@ -0,0 +1,145 @@
+// Font formats fontize knows about, and telling them apart.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::{legacy, sfnt, woff};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontKind { Otf, Ttf, Woff, Woff2, Collection, Pcf, Bdf, Type1 }
+
+impl FontKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            FontKind::Otf => "otf",
+            FontKind::Ttf => "ttf",
+            FontKind::Woff => "woff",
+            FontKind::Woff2 => "woff2",
+            FontKind::Collection => "ttc",
+            FontKind::Pcf => "pcf",
+            FontKind::Bdf => "bdf",
+            FontKind::Type1 => "type1",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FontKind> {
+        match name {
+            "otf" => Some(FontKind::Otf),
+            "ttf" => Some(FontKind::Ttf),
+            "woff" => Some(FontKind::Woff),
+            "woff2" => Some(FontKind::Woff2),
+            "ttc" => Some(FontKind::Collection),
+            "pcf" => Some(FontKind::Pcf),
+            "bdf" => Some(FontKind::Bdf),
+            "type1" => Some(FontKind::Type1),
+            _ => None,
+        }
+    }
+}
+
+/// Works out a font file's format from its extension, or failing that from
+/// its first bytes (looking through gzip for compressed bitmap fonts).
+pub fn detect_kind(path: &Path) -> Result<FontKind> {
+    // Try extension first (case-insensitive)
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase()) {
+        match ext.as_str() {
+            "otf" => return Ok(FontKind::Otf),
+            "ttf" => return Ok(FontKind::Ttf),
+            "ttc" | "otc" => return Ok(FontKind::Collection),
+            "woff" => return Ok(FontKind::Woff),
+            "woff2" => return Ok(FontKind::Woff2),
+            "pcf" => return Ok(FontKind::Pcf),
+            "bdf" => return Ok(FontKind::Bdf),
+            "pfb" | "pfa" => return Ok(FontKind::Type1),
+            _ => {}
+        }
+    }
+    // Fallback: sniff magic bytes
+    let mut f = File::open(path)?;
+    let mut head = Vec::with_capacity(64);
+    f.by_ref().take(64).read_to_end(&mut head)?;
+    if head.len() < 4 {
+        return Err(Error::UnknownFormat(path.to_path_buf()));
+    }
+    // Bitmap fonts are commonly gzipped as a whole (.pcf.gz)
+    if head.starts_with(&[0x1f, 0x8b]) {
+        head.clear();
+        let mut gz = flate2::read::GzDecoder::new(File::open(path)?).take(64);
+        if gz.read_to_end(&mut head).is_err() || head.len() < 4 {
+            return Err(Error::UnknownFormat(path.to_path_buf()));
+        }
+    }
+    let magic = [head[0], head[1], head[2], head[3]];
+
+    if &magic == b"OTTO" {
+        return Ok(FontKind::Otf);
+    }
+    if magic == [0x00, 0x01, 0x00, 0x00] || &magic == b"true" {
+        return Ok(FontKind::Ttf);
+    }
+    if &magic == b"ttcf" {
+        return Ok(FontKind::Collection);
+    }
+    if &magic == b"wOFF" {
+        return Ok(FontKind::Woff);
+    }
+    if &magic == b"wOF2" {
+        return Ok(FontKind::Woff2);
+    }
+    if magic == legacy::PCF_MAGIC {
+        return Ok(FontKind::Pcf);
+    }
+    if head.starts_with(b"STARTFONT") {
+        return Ok(FontKind::Bdf);
+    }
+    if head.starts_with(&[0x80, 0x01]) || head.starts_with(b"%!PS-AdobeFont") || head.starts_with(b"%!FontType1") {
+        return Ok(FontKind::Type1);
+    }
+    Err(Error::UnknownFormat(path.to_path_buf()))
+}
+
+pub(crate) fn has_font_extension(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_lowercase();
+    if name.ends_with(".pcf.gz") || name.ends_with(".bdf.gz") {
+        return true;
+    }
+    matches!(
+        path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase()).as_deref(),
+        Some("otf" | "ttf" | "ttc" | "otc" | "woff" | "woff2" | "pcf" | "bdf" | "pfb" | "pfa")
+    )
+}
+
+/// Type 1 font metrics (.afm/.pfm), which travel with the outline file.
+pub(crate) fn is_metrics(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase()).as_deref(),
+        Some("afm" | "pfm")
+    )
+}
+
+/// Metric files next to `font` that share its stem, ignoring case.
+pub(crate) fn metric_files(font: &Path) -> Vec<PathBuf> {
+    let stem = font.file_stem().map(|s| s.to_string_lossy().to_lowercase());
+    let dir = font.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut found = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| is_metrics(p) && p.file_stem().map(|s| s.to_string_lossy().to_lowercase()) == stem)
+        .collect::<Vec<_>>();
+    found.sort();
+    found
+}
+
+/// Names of the font at `path`, whatever its format; web fonts are unpacked
+/// to the sfnt they wrap first.
+pub(crate) fn read_names(path: &Path) -> Result<sfnt::Names> {
+    Ok(match detect_kind(path)? {
+        FontKind::Pcf | FontKind::Bdf | FontKind::Type1 => legacy::read_names(path)?,
+        FontKind::Woff => sfnt::Font::parse(&woff::decode_woff(&fs::read(path)?)?)?.names(),
+        FontKind::Woff2 => sfnt::Font::parse(&woff::decode_woff2(&fs::read(path)?)?)?.names(),
+        _ => sfnt::read_names(path)?,
+    })
+}
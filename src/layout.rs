@@ -0,0 +1,195 @@
+// Where installed fonts go: one base directory per scope, and below it the
+// directories a template names, by default one per format and family.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::FontKind;
+use crate::error::{Error, Result};
+use crate::files::path_component;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope { User, System }
+
+impl Scope {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::User => "user",
+            Scope::System => "system",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Scope> {
+        match name {
+            "user" => Some(Scope::User),
+            "system" => Some(Scope::System),
+            _ => None,
+        }
+    }
+}
+
+pub fn user_fonts_base() -> PathBuf {
+    if let Some(xdg) = env::var_os("XDG_DATA_HOME") {
+        PathBuf::from(xdg).join("fonts")
+    } else if let Some(home) = env::var_os("HOME") {
+        PathBuf::from(home).join(".local/share/fonts")
+    } else {
+        PathBuf::from(".local/share/fonts")
+    }
+}
+
+pub fn system_fonts_base() -> PathBuf {
+    PathBuf::from("/usr/share/fonts")
+}
+
+// Scopes an operation looks at: `--user` restricts it to the user's fonts
+pub fn scopes(scope: Scope) -> Vec<Scope> {
+    match scope {
+        Scope::User => vec![Scope::User],
+        Scope::System => vec![Scope::User, Scope::System],
+    }
+}
+
+/// The directories below the base a font goes into, unless configured
+/// otherwise: `OTF/Fira Code/`.
+pub const DEFAULT_TEMPLATE: &str = "{format}/{family}";
+
+/// Base directories fonts are installed under, laid out below the base as
+/// `template` says.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub user_base: PathBuf,
+    pub system_base: PathBuf,
+    /// `/`-separated directories, in which `{format}` stands for the format
+    /// directory (`OTF`, `TTF`, `misc`…), `{kind}` for the format's short
+    /// name (`otf`, `ttf`, `pcf`…) and `{family}` for the family name. A
+    /// directory naming the family is left out for fonts without one; an
+    /// empty template puts every font directly in the base.
+    pub template: String,
+    /// A staging tree standing in for `/`, as for packages and images: every
+    /// path, manifests included, is taken to be below it.
+    pub root: Option<PathBuf>,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            user_base: user_fonts_base(),
+            system_base: system_fonts_base(),
+            template: DEFAULT_TEMPLATE.into(),
+            root: None,
+        }
+    }
+}
+
+/// Checks that `template` only names directories below the base, with
+/// placeholders [`Layout::dest_dir`] knows.
+pub fn check_template(template: &str) -> Result<()> {
+    let bad = |why: &str| Err(Error::Config(format!("layout template {template:?}: {why}")));
+    if template.is_empty() {
+        return Ok(());
+    }
+    for segment in template.split('/') {
+        if matches!(segment, "" | "." | "..") {
+            return bad("directories must be named, and stay below the base");
+        }
+        let mut rest = segment;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else { return bad("unclosed {") };
+            if !matches!(&rest[start + 1..start + end], "format" | "kind" | "family") {
+                return bad("placeholders are {format}, {kind} and {family}");
+            }
+            rest = &rest[start + end + 1..];
+        }
+    }
+    Ok(())
+}
+
+impl Layout {
+    pub fn base(&self, scope: Scope) -> PathBuf {
+        match scope {
+            Scope::User => self.staged(&self.user_base),
+            Scope::System => self.staged(&self.system_base),
+        }
+    }
+
+    /// Where `path` of the target system is found: below the staging root,
+    /// if there is one.
+    pub fn staged(&self, path: &Path) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(path.strip_prefix("/").unwrap_or(path)),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// What a path below the staging root will be on the target system.
+    pub fn deployed(&self, path: &Path) -> PathBuf {
+        match self.root.as_deref().and_then(|root| path.strip_prefix(root).ok()) {
+            Some(rest) => Path::new("/").join(rest),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// The manifest of fonts installed into `scope`.
+    pub fn manifest(&self, scope: Scope) -> PathBuf {
+        self.staged(&crate::manifest::manifest_path(scope))
+    }
+
+    /// Every directory fonts of `scope` are found in: the base fontize
+    /// installs into, then the ones other tools and older setups use.
+    pub fn font_dirs(&self, scope: Scope) -> Vec<PathBuf> {
+        match scope {
+            Scope::User => {
+                let mut dirs = vec![self.base(scope)];
+                dirs.extend(env::var_os("HOME").map(|home| self.staged(&Path::new(&home).join(".fonts"))));
+                dirs
+            }
+            Scope::System => vec![self.base(scope), self.staged(Path::new("/usr/local/share/fonts"))],
+        }
+    }
+
+    /// The directory a font of `kind` and `family` goes into; fonts without a
+    /// usable family name skip the family's directory.
+    pub fn dest_dir(&self, scope: Scope, kind: FontKind, family: Option<&str>) -> PathBuf {
+        let subdir = match kind {
+            FontKind::Otf => "OTF",
+            FontKind::Ttf => "TTF",
+            FontKind::Woff => "WOFF",
+            FontKind::Woff2 => "WOFF2",
+            FontKind::Collection => "TTC",
+            // Where fontconfig setups conventionally keep these
+            FontKind::Pcf | FontKind::Bdf => "misc",
+            FontKind::Type1 => "Type1",
+        };
+        let family = family.and_then(path_component);
+        let mut dir = self.base(scope);
+        for segment in self.template.split('/') {
+            let mut name = segment.replace("{format}", subdir).replace("{kind}", kind.name());
+            if name.contains("{family}") {
+                let Some(family) = &family else { continue };
+                name = name.replace("{family}", family);
+            }
+            dir.extend(path_component(&name));
+        }
+        dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_known_placeholders_below_the_base() {
+        for template in ["", DEFAULT_TEMPLATE, "{family}", "fonts/{kind}-{format}/{family}", "fixed"] {
+            assert!(check_template(template).is_ok(), "{template:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_escapes_and_unknown_placeholders() {
+        for template in ["/{family}", "{family}/", "a//b", "./a", "a/../b", "..", "{style}", "{family", "x{}"] {
+            assert!(matches!(check_template(template), Err(Error::Config(_))), "{template:?}");
+        }
+    }
+}
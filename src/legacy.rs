@@ -0,0 +1,331 @@
+// Legacy X11 bitmap (PCF, BDF) and PostScript Type 1 (PFB, PFA) fonts.
+// These have no name table; their family and style live in PCF properties,
+// BDF header lines or the Type 1 cleartext dictionary, which is all fontize
+// needs to file them away. Parsing doubles as a structural sanity check.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+
+use crate::sfnt::Names;
+use crate::woff::MAX_SFNT_SIZE;
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+pub const PCF_MAGIC: [u8; 4] = [0x01, b'f', b'c', b'p'];
+
+/// Undoes the gzip layer bitmap fonts are usually shipped with.
+pub fn gunzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    if !data.starts_with(&[0x1f, 0x8b]) {
+        return Ok(data.to_vec());
+    }
+    let mut out = Vec::new();
+    GzDecoder::new(data).take(MAX_SFNT_SIZE + 1).read_to_end(&mut out)?;
+    if out.len() as u64 > MAX_SFNT_SIZE {
+        return Err(invalid(format!("Gzipped font unpacks to more than {} MiB", MAX_SFNT_SIZE >> 20)));
+    }
+    Ok(out)
+}
+
+pub fn pcf_names(data: &[u8]) -> io::Result<Names> {
+    const PCF_PROPERTIES: u32 = 1;
+    const PCF_BYTE_MASK: u32 = 1 << 2;
+
+    let le = |offset: usize| -> io::Result<u32> {
+        let b = data.get(offset..offset + 4).ok_or_else(|| invalid("Truncated PCF file"))?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    if !data.starts_with(&PCF_MAGIC) {
+        return Err(invalid("Not a PCF font"));
+    }
+    let count = le(4)? as usize;
+    let mut properties = None;
+    for i in 0..count {
+        let entry = 8 + i * 16;
+        let (kind, size, offset) = (le(entry)?, le(entry + 8)? as usize, le(entry + 12)? as usize);
+        if offset.checked_add(size).is_none_or(|end| end > data.len()) {
+            return Err(invalid("PCF table runs past the end of the file; the file is probably truncated"));
+        }
+        if kind == PCF_PROPERTIES {
+            properties = Some(offset);
+        }
+    }
+    let table = properties.ok_or_else(|| invalid("PCF font has no properties table"))?;
+
+    let format = le(table)?;
+    let u32_at = |offset: usize| -> io::Result<u32> {
+        let b = data.get(offset..offset + 4).ok_or_else(|| invalid("Truncated PCF properties"))?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if format & PCF_BYTE_MASK != 0 { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    };
+    let nprops = u32_at(table + 4)? as usize;
+    let props = table + 8;
+    let padding = if nprops & 3 != 0 { 4 - (nprops & 3) } else { 0 };
+    let strings_size_at = props + nprops * 9 + padding;
+    let strings = strings_size_at + 4;
+    let strings_size = u32_at(strings_size_at)? as usize;
+    let pool = data.get(strings..strings + strings_size).ok_or_else(|| invalid("Truncated PCF properties"))?;
+    let string = |offset: u32| {
+        let rest = pool.get(offset as usize..)?;
+        let end = rest.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&rest[..end]).into_owned())
+    };
+
+    let mut found = Vec::new();
+    for i in 0..nprops {
+        let prop = props + i * 9;
+        let name = string(u32_at(prop)?);
+        let is_string = data.get(prop + 4).copied().unwrap_or(0) != 0;
+        if let (Some(name), true) = (name, is_string) {
+            found.push((name, string(u32_at(prop + 5)?)));
+        }
+    }
+    let get = |key: &str| found.iter().find(|(k, _)| k == key).and_then(|(_, v)| v.clone());
+    Ok(xlfd_names(get("FAMILY_NAME"), get("WEIGHT_NAME"), get("FONT")))
+}
+
+pub fn bdf_names(data: &[u8]) -> io::Result<Names> {
+    let text = String::from_utf8_lossy(data);
+    if !text.starts_with("STARTFONT") {
+        return Err(invalid("Not a BDF font"));
+    }
+    if !text.trim_end().ends_with("ENDFONT") {
+        return Err(invalid("BDF font has no ENDFONT line; the file is probably truncated"));
+    }
+    let value = |key: &str| {
+        text.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(' '))
+            .map(|v| v.trim().trim_matches('"').to_string())
+    };
+    Ok(xlfd_names(value("FAMILY_NAME"), value("WEIGHT_NAME"), value("FONT")))
+}
+
+// Bitmap fonts are named by XLFD (-foundry-family-weight-...); its family
+// field stands in when FAMILY_NAME is missing
+fn xlfd_names(family: Option<String>, weight: Option<String>, font: Option<String>) -> Names {
+    let fields = font.as_deref().map(|f| f.split('-').collect::<Vec<_>>()).unwrap_or_default();
+    let field = |i: usize| fields.get(i).filter(|f| !f.is_empty()).map(|f| f.to_string());
+    Names {
+        family: family.or_else(|| field(2)),
+        subfamily: weight.or_else(|| field(3)),
+        postscript: font,
+        version: None,
+    }
+}
+
+/// The cleartext part of a Type 1 font: the first PFB segment, or a PFA up
+/// to `eexec`. PFB segment framing is checked on the way.
+fn type1_cleartext(data: &[u8]) -> io::Result<String> {
+    if data.first() == Some(&0x80) {
+        let mut pos = 0;
+        let mut first = None;
+        loop {
+            // The end-of-file marker is only the two bytes 0x80 0x03
+            let marker = data.get(pos..pos + 2)
+                .ok_or_else(|| invalid("PFB segment runs past the end of the file; the file is probably truncated"))?;
+            if marker[0] != 0x80 {
+                return Err(invalid("Corrupt PFB segment header"));
+            }
+            if marker[1] == 3 {
+                break;
+            }
+            let header = data.get(pos..pos + 6)
+                .ok_or_else(|| invalid("PFB segment runs past the end of the file; the file is probably truncated"))?;
+            let len = u32::from_le_bytes([header[2], header[3], header[4], header[5]]) as usize;
+            let body = data.get(pos + 6..pos + 6 + len)
+                .ok_or_else(|| invalid("PFB segment runs past the end of the file; the file is probably truncated"))?;
+            if header[1] == 1 && first.is_none() {
+                first = Some(body);
+            }
+            pos += 6 + len;
+        }
+        let text = first.ok_or_else(|| invalid("PFB font has no cleartext segment"))?;
+        return Ok(String::from_utf8_lossy(text).into_owned());
+    }
+    let text = String::from_utf8_lossy(data);
+    if !text.starts_with("%!PS-AdobeFont") && !text.starts_with("%!FontType1") {
+        return Err(invalid("Not a Type 1 font"));
+    }
+    let end = text.find("eexec").ok_or_else(|| invalid("PFA font has no eexec section"))?;
+    Ok(text[..end].to_string())
+}
+
+pub fn type1_names(data: &[u8]) -> io::Result<Names> {
+    let text = type1_cleartext(data)?;
+    // Entries look like `/FamilyName (Nimbus Sans) readonly def` or `/FontName /NimbusSans-Bold def`
+    let value = |key: &str| {
+        let rest = &text[text.find(key)? + key.len()..];
+        let rest = rest.trim_start();
+        if let Some(rest) = rest.strip_prefix('(') {
+            Some(rest[..rest.find(')')?].to_string())
+        } else {
+            let rest = rest.strip_prefix('/')?;
+            Some(rest.split(|c: char| c.is_whitespace()).next()?.to_string())
+        }
+    };
+    let postscript = value("/FontName");
+    if postscript.is_none() {
+        return Err(invalid("Type 1 font has no /FontName"));
+    }
+    Ok(Names {
+        family: value("/FamilyName"),
+        subfamily: value("/Weight"),
+        postscript,
+        version: value("/version"),
+    })
+}
+
+/// Names of any legacy font file, whichever of the formats it is in.
+pub fn read_names(path: &Path) -> io::Result<Names> {
+    let data = gunzip(&fs::read(path)?)?;
+    if data.starts_with(&PCF_MAGIC) {
+        pcf_names(&data)
+    } else if data.starts_with(b"STARTFONT") {
+        bdf_names(&data)
+    } else {
+        type1_names(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    const XLFD: &str = "-misc-Terminus-bold-r-normal--16-160-72-72-c-80-iso10646-1";
+
+    // A PCF holding only a properties table; string values are (name, value)
+    fn pcf(properties: &[(&str, &str)], big_endian: bool) -> Vec<u8> {
+        let word = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let mut pool = Vec::new();
+        let mut props = Vec::new();
+        for (name, value) in properties {
+            props.extend_from_slice(&word(pool.len() as u32));
+            pool.extend_from_slice(name.as_bytes());
+            pool.push(0);
+            props.push(1);
+            props.extend_from_slice(&word(pool.len() as u32));
+            pool.extend_from_slice(value.as_bytes());
+            pool.push(0);
+        }
+        props.resize(props.len().next_multiple_of(4), 0);
+
+        let mut table = (if big_endian { 1u32 << 2 } else { 0 }).to_le_bytes().to_vec();
+        table.extend_from_slice(&word(properties.len() as u32));
+        table.extend(props);
+        table.extend_from_slice(&word(pool.len() as u32));
+        table.extend(pool);
+
+        let mut data = PCF_MAGIC.to_vec();
+        for v in [1, 1, 0, table.len() as u32, 24] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend(table);
+        data
+    }
+
+    fn bdf(properties: &str) -> String {
+        format!("STARTFONT 2.1\nFONT {XLFD}\nSIZE 16 72 72\nSTARTPROPERTIES 2\n{properties}ENDPROPERTIES\nCHARS 0\nENDFONT\n")
+    }
+
+    const PFA: &str = "%!PS-AdobeFont-1.0: NimbusSans-Bold 1.00\n\
+        /FontInfo 8 dict dup begin\n\
+        /version (1.00) readonly def\n\
+        /FamilyName (Nimbus Sans) readonly def\n\
+        /Weight (Bold) readonly def\n\
+        end readonly def\n\
+        /FontName /NimbusSans-Bold def\n\
+        currentfile eexec\n";
+
+    fn pfb(segments: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (kind, body) in segments {
+            data.extend_from_slice(&[0x80, *kind]);
+            data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            data.extend_from_slice(body);
+        }
+        data.extend_from_slice(&[0x80, 3]);
+        data
+    }
+
+    #[test]
+    fn reads_pcf_properties_in_either_byte_order() {
+        for big_endian in [false, true] {
+            let data = pcf(&[("FONT", XLFD), ("FAMILY_NAME", "Terminus"), ("WEIGHT_NAME", "Bold")], big_endian);
+            let names = pcf_names(&data).unwrap();
+            assert_eq!(names.family.as_deref(), Some("Terminus"));
+            assert_eq!(names.subfamily.as_deref(), Some("Bold"));
+            assert_eq!(names.postscript.as_deref(), Some(XLFD));
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_xlfd() {
+        let names = pcf_names(&pcf(&[("FONT", XLFD)], false)).unwrap();
+        assert_eq!(names.family.as_deref(), Some("Terminus"));
+        assert_eq!(names.subfamily.as_deref(), Some("bold"));
+        let names = bdf_names(bdf("").as_bytes()).unwrap();
+        assert_eq!(names.family.as_deref(), Some("Terminus"));
+        assert_eq!(names.subfamily.as_deref(), Some("bold"));
+    }
+
+    #[test]
+    fn refuses_truncated_pcf() {
+        let data = pcf(&[("FONT", XLFD)], false);
+        let error = pcf_names(&data[..data.len() - 4]).unwrap_err();
+        assert!(error.to_string().contains("probably truncated"), "{error}");
+        assert!(pcf_names(&data[..6]).is_err());
+        assert!(pcf_names(b"STARTFONT").is_err());
+    }
+
+    #[test]
+    fn reads_bdf_headers() {
+        let names = bdf_names(bdf("FAMILY_NAME \"Terminus TTF\"\nWEIGHT_NAME \"Medium\"\n").as_bytes()).unwrap();
+        assert_eq!(names.family.as_deref(), Some("Terminus TTF"));
+        assert_eq!(names.subfamily.as_deref(), Some("Medium"));
+        assert_eq!(names.postscript.as_deref(), Some(XLFD));
+
+        let text = bdf("");
+        let error = bdf_names(&text.as_bytes()[..text.len() - 8]).unwrap_err();
+        assert!(error.to_string().contains("no ENDFONT"), "{error}");
+    }
+
+    #[test]
+    fn reads_type1_cleartext() {
+        let binary = [0x5a, 0x00, 0xff];
+        for data in [PFA.as_bytes().to_vec(), pfb(&[(1, PFA.as_bytes()), (2, &binary)])] {
+            let names = type1_names(&data).unwrap();
+            assert_eq!(names.family.as_deref(), Some("Nimbus Sans"));
+            assert_eq!(names.subfamily.as_deref(), Some("Bold"));
+            assert_eq!(names.postscript.as_deref(), Some("NimbusSans-Bold"));
+            assert_eq!(names.version.as_deref(), Some("1.00"));
+        }
+    }
+
+    #[test]
+    fn refuses_broken_type1() {
+        let data = pfb(&[(1, PFA.as_bytes()), (2, &[0; 16])]);
+        let error = type1_names(&data[..data.len() - 8]).unwrap_err();
+        assert!(error.to_string().contains("probably truncated"), "{error}");
+        assert!(type1_names(&pfb(&[(2, &[0; 16])])).is_err());
+        assert!(type1_names(PFA.replace("/FontName /NimbusSans-Bold def\n", "").as_bytes()).is_err());
+        assert!(type1_names(PFA.replace("eexec", "").as_bytes()).is_err());
+        assert!(type1_names(b"%!PS-Adobe-3.0\n").is_err());
+    }
+
+    #[test]
+    fn gunzips_only_gzip() {
+        let data = bdf("");
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(data.as_bytes()).unwrap();
+        assert_eq!(gunzip(&gz.finish().unwrap()).unwrap(), data.as_bytes());
+        assert_eq!(gunzip(data.as_bytes()).unwrap(), data.as_bytes());
+    }
+}
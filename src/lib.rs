@@ -0,0 +1,34 @@
+//! Font installation for Linux: detects font formats, unpacks archives and
+//! web fonts, validates fonts, files them under a per-format, per-family
+//! layout, keeps a manifest of what was installed and refreshes fontconfig's
+//! cache. The `fontize` binary is a thin command-line wrapper around
+//! [`Installer`].
+
+mod archive;
+mod cache;
+mod error;
+mod files;
+mod helper;
+mod inspect;
+mod install;
+mod kind;
+mod layout;
+mod legacy;
+pub mod manifest;
+mod search;
+mod sfnt;
+mod validate;
+mod woff;
+
+pub use cache::{CacheRefresh, CacheStatus, Refresh, USER_CACHE_ENV, refresh_font_cache, refresh_staged_cache};
+pub use error::{Error, Result};
+pub use helper::serve_helper;
+pub use inspect::{Axis, FaceInfo, FontInfo, NameRecord, inspect, script_name};
+pub use install::{
+    Action, ConflictPolicy, Event, Failure, FontReport, InstallReport, Installer, UninstallReport, matches_font,
+    query_path,
+};
+pub use kind::{FontKind, detect_kind};
+pub use layout::{DEFAULT_TEMPLATE, Layout, Scope, check_template, scopes, system_fonts_base, user_fonts_base};
+pub use search::{Found, Installed, ListFilter, list, search};
+pub use sfnt::Names;
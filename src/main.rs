@@ -1,175 +1,803 @@
 use std::env;
 use std::fs;
-use std::fs::File;
-use std::io::{self, Read};
-use std::os::unix::fs::PermissionsExt;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
-#[derive(Debug, Clone, Copy)]
-enum FontKind { Otf, Ttf }
+use clap::{CommandFactory, Parser};
+use fontize::{
+    Action, CacheRefresh, CacheStatus, Error, Event, Failure, Found, InstallReport, FontInfo, Installed, Installer, Layout, ListFilter, Names, Refresh, Result, Scope, manifest,
+    matches_font, query_path, scopes,
+};
+use nix::unistd::geteuid;
+use serde_json::json;
 
-fn detect_kind(path: &Path) -> io::Result<FontKind> {
-    // Try extension first (case-insensitive)
-    if let Some(ext) = path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase()) {
-        match ext.as_str() {
-            "otf" => return Ok(FontKind::Otf),
-            "ttf" | "ttc" => return Ok(FontKind::Ttf),
-            _ => {}
-        }
+mod cli;
+mod config;
+mod escalate;
+mod output;
+
+use cli::{CacheCommand, Cli, Command, ConfigCommand, ListArgs, OutputFormat, ScopeChoice};
+use config::Config;
+use escalate::{Backend, escalate_and_reexec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity { Quiet, Normal, Verbose }
+
+fn print_faces(faces: &[Names]) {
+    for (i, face) in faces.iter().enumerate() {
+        println!(
+            "    face {i}: {} {}",
+            face.family.as_deref().unwrap_or("(unnamed)"),
+            face.subfamily.as_deref().unwrap_or("")
+        );
     }
-    // Fallback: sniff magic bytes
-    let mut f = File::open(path)?;
-    let mut magic = [0u8; 4];
-    f.read_exact(&mut magic)?;
+}
 
-    if &magic == b"OTTO" {
-        return Ok(FontKind::Otf);
+fn describe_dirs(dirs: &[PathBuf]) -> String {
+    if dirs.is_empty() {
+        return "all font directories".into();
     }
-    if magic == [0x00, 0x01, 0x00, 0x00] || &magic == b"true" || &magic == b"ttcf" {
-        return Ok(FontKind::Ttf);
+    dirs.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn print_refresh(refresh: &Refresh) {
+    if refresh.fallback {
+        eprintln!(
+            "Warning: fc-cache not found; marked {} stale for fontconfig to rescan on next use",
+            describe_dirs(&refresh.dirs)
+        );
+        return;
     }
-    Err(io::Error::new(io::ErrorKind::InvalidData, "Unknown font format (not OTF/TTF)"))
+    let user = refresh.invoking_user.as_ref().map(|user| format!(" and {user}'s")).unwrap_or_default();
+    println!(
+        "{} the {} font cache{user} for {} in {:.2}s",
+        if refresh.forced { "Rebuilt" } else { "Refreshed" },
+        refresh.scope.name(),
+        describe_dirs(&refresh.dirs),
+        refresh.elapsed.as_secs_f64()
+    );
 }
 
-fn unique_path(dest: PathBuf) -> PathBuf {
-    if !dest.exists() {
-        return dest;
+fn print_cache(cache: &CacheStatus, verbosity: Verbosity) {
+    match cache {
+        CacheStatus::Refreshed(refresh) if refresh.fallback || verbosity == Verbosity::Verbose => print_refresh(refresh),
+        _ => {}
     }
-    let stem = dest.file_stem().and_then(|s| s.to_str()).unwrap_or("font");
-    let ext = dest.extension().and_then(|e| e.to_str()).unwrap_or("");
-    for i in 1.. {
-        let candidate = if ext.is_empty() {
-            dest.with_file_name(format!("{stem}-{i}"))
-        } else {
-            dest.with_file_name(format!("{stem}-{i}.{ext}"))
-        };
-        if !candidate.exists() {
-            return candidate;
+}
+
+// A batch fails with the exit code its failures share, or 1 if they differ
+fn batch_exit_code(failures: &[Failure]) -> i32 {
+    let mut codes = failures.iter().map(|failure| failure.error.exit_code());
+    let first = codes.next().unwrap_or(1);
+    if codes.all(|code| code == first) { first } else { 1 }
+}
+
+// The tool a privileged run would go through, if there is one
+fn escalation_tool(backend: Backend, interactive: bool) -> Option<&'static str> {
+    backend.resolve(interactive).ok().map(|(backend, _)| backend.name())
+}
+
+// Settles --scope=auto for a command that changes fonts: system-wide when
+// that needs no password, the user's fonts otherwise; also says why, and
+// which backend gets root rights without asking
+fn auto_scope(backend: Backend) -> (Scope, Backend, String) {
+    if geteuid().is_root() {
+        return (Scope::System, backend, "running as root".into());
+    }
+    if backend.enabled()
+        && let Some(found) = backend.passwordless()
+    {
+        return (Scope::System, found, format!("{} needs no password", found.name()));
+    }
+    (Scope::User, backend, "system-wide fonts need root rights".into())
+}
+
+// Installs, or with `dry_run` plans, `paths`; in NDJSON mode each font and
+// failure is written as soon as it has been handled
+fn run_installer(
+    installer: &Installer,
+    paths: &[PathBuf],
+    dry_run: bool,
+    format: OutputFormat,
+) -> Result<InstallReport> {
+    let stream = |event: Event| {
+        if format != OutputFormat::Ndjson {
+            return;
+        }
+        match event {
+            Event::Font(font) => output::record("font", output::font(font)),
+            Event::Failure(failure) => output::record("failure", output::failure(failure)),
+        }
+    };
+    if dry_run { installer.plan_with(paths, stream) } else { installer.install_with(paths, stream) }
+}
+
+// Machine-readable rendering of an install or plan: one document for JSON,
+// or the records that follow the streamed fonts and failures for NDJSON
+fn print_install_report(
+    installer: &Installer,
+    report: &InstallReport,
+    dry_run: bool,
+    escalate: Option<&str>,
+    format: OutputFormat,
+) {
+    let docs = report.docs.iter().map(|(doc, dest)| output::doc(doc, dest));
+    match format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => output::document(&json!({
+            "dry_run": dry_run,
+            "scope": installer.scope().name(),
+            "fonts": report.fonts.iter().map(output::font).collect::<Vec<_>>(),
+            "docs": docs.collect::<Vec<_>>(),
+            "failures": report.failures.iter().map(output::failure).collect::<Vec<_>>(),
+            "warnings": report.warnings,
+            "escalate": escalate,
+            "cache_refresh": output::cache(&report.cache),
+        })),
+        OutputFormat::Ndjson => {
+            for doc in docs {
+                output::record("doc", doc);
+            }
+            for warning in &report.warnings {
+                output::record("warning", json!({ "message": warning }));
+            }
+            output::record("summary", json!({
+                "dry_run": dry_run,
+                "scope": installer.scope().name(),
+                "installed": report.count(Action::Installed),
+                "replaced": report.count(Action::Replaced),
+                "skipped": report.count(Action::Skipped),
+                "failed": report.failures.len(),
+                "escalate": escalate,
+                "cache_refresh": output::cache(&report.cache),
+            }));
         }
     }
-    unreachable!()
 }
 
-fn move_across_fs(src: &Path, dst: &Path) -> io::Result<()> {
-    match fs::rename(src, dst) {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            if e.raw_os_error() == Some(18) /* EXDEV */ {
-                fs::copy(src, dst)?;
-                fs::remove_file(src)?;
-                Ok(())
-            } else {
-                Err(e)
+// Installs `paths`; `helper` is why no privileged helper could be set up
+// for a system-wide install, if one was wanted
+fn do_install_batch(
+    installer: &Installer,
+    paths: &[PathBuf],
+    helper: Option<Error>,
+    verbosity: Verbosity,
+    format: OutputFormat,
+) -> Result<()> {
+    let report = run_installer(installer, paths, false, format)?;
+    let quiet = verbosity == Verbosity::Quiet;
+    // Denied fonts are down to the missing helper, not to permissions alone
+    let helper = helper.filter(|_| {
+        report.failures.iter().any(|failure| matches!(failure.error, Error::PermissionDenied(_)))
+    });
+
+    if format != OutputFormat::Text {
+        print_install_report(installer, &report, false, None, format);
+        let code = match (&report.cache, &helper) {
+            (_, Some(e)) => e.exit_code(),
+            _ if !report.failures.is_empty() => batch_exit_code(&report.failures),
+            (CacheStatus::Failed(e), _) => e.exit_code(),
+            _ => 0,
+        };
+        std::process::exit(code);
+    }
+
+    for warning in &report.warnings {
+        eprintln!("Warning: {warning}");
+    }
+    for failure in &report.failures {
+        eprintln!("Failed {}: {}", failure.source.display(), failure.error);
+    }
+    for font in &report.fonts {
+        for warning in &font.warnings {
+            eprintln!("Warning: {}: {warning}", font.source.display());
+        }
+        if quiet {
+            continue;
+        }
+        match font.action {
+            Action::Skipped => {
+                println!("Skipped {} ({})", font.source.display(), font.note.unwrap_or("already installed"));
+                continue;
+            }
+            Action::Installed => println!("Installed {} -> {}", font.source.display(), font.destination.display()),
+            Action::Replaced => println!("Replaced {} -> {}", font.source.display(), font.destination.display()),
+        }
+        if verbosity == Verbosity::Verbose {
+            print!("    {}", font.kind.name());
+            if let Some(from) = font.converted_from {
+                print!(" (from {})", from.name());
             }
+            for name in [&font.names.family, &font.names.subfamily, &font.names.version].into_iter().flatten() {
+                print!(", {name}");
+            }
+            println!();
+            println!("    sha256 {}", font.sha256);
+            for (_, dest) in &font.metrics {
+                println!("    metrics -> {}", dest.display());
+            }
+        }
+        print_faces(&font.faces);
+    }
+    if !quiet {
+        for (doc, dest) in &report.docs {
+            println!("Installed {} -> {}", doc.display(), dest.display());
         }
     }
+    print_cache(&report.cache, verbosity);
+
+    let attempted = report.fonts.len() + report.failures.len();
+    if attempted > 1 && !quiet {
+        println!(
+            "Installed {} font(s), {} skipped, {} failed",
+            report.count(Action::Installed) + report.count(Action::Replaced),
+            report.count(Action::Skipped),
+            report.failures.len()
+        );
+    }
+    if let Some(e) = helper {
+        return Err(e);
+    }
+    if !report.failures.is_empty() {
+        if let CacheStatus::Failed(e) = &report.cache {
+            eprintln!("Warning: {e}");
+        }
+        std::process::exit(batch_exit_code(&report.failures));
+    }
+    match report.cache {
+        CacheStatus::Failed(e) => Err(e),
+        _ => Ok(()),
+    }
 }
 
-fn set_permissions644(path: &Path) -> io::Result<()> {
-    let mut perms = fs::metadata(path)?.permissions();
-    perms.set_mode(0o644);
-    fs::set_permissions(path, perms)
+fn do_dry_run(
+    installer: &Installer,
+    paths: &[PathBuf],
+    backend: Backend,
+    interactive: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let report = run_installer(installer, paths, true, format)?;
+    let scope = installer.scope();
+    let escalate = scope == Scope::System && report.needs_privileges;
+
+    if format != OutputFormat::Text {
+        let tool = escalate.then(|| escalation_tool(backend, interactive)).flatten();
+        print_install_report(installer, &report, true, tool, format);
+        dry_run_exit(&report);
+        return Ok(());
+    }
+
+    println!("Dry run: nothing will be changed ({} scope)", scope.name());
+    for plan in &report.fonts {
+        let action = output::action_name(plan.action);
+        print!("  {action:<8} {} -> {} [{}", plan.source.display(), plan.destination.display(), plan.kind.name());
+        if let Some(from) = plan.converted_from {
+            print!(" from {}", from.name());
+        }
+        if let Some(family) = &plan.names.family {
+            print!(", {family}");
+        }
+        match plan.note {
+            Some(note) => println!("; {note}]"),
+            None => println!("]"),
+        }
+        print_faces(&plan.faces);
+        for (metrics, dest) in &plan.metrics {
+            println!("    metrics: {} -> {}", metrics.display(), dest.display());
+        }
+        for warning in &plan.warnings {
+            println!("    warning: {warning}");
+        }
+    }
+    for warning in &report.warnings {
+        println!("  warning: {warning}");
+    }
+    for (doc, dest) in &report.docs {
+        println!("  {:<8} {} -> {}", "doc", doc.display(), dest.display());
+    }
+    for failure in &report.failures {
+        println!("  {:<8} {}: {}", "fail", failure.source.display(), failure.error);
+    }
+    if escalate {
+        let base = installer.base();
+        match backend.resolve(interactive) {
+            Ok((backend, _)) => {
+                println!("Would write through a privileged helper run with {}: {} is not writable", backend.name(), base.display())
+            }
+            Err(e) => println!("Would fail: {} is not writable. {e}", base.display()),
+        }
+    }
+    if let CacheStatus::Pending(dirs) = &report.cache {
+        println!("Would refresh the font cache for {}", describe_dirs(dirs));
+    } else {
+        println!("No font cache refresh needed");
+    }
+    dry_run_exit(&report);
+    Ok(())
 }
 
-fn refresh_font_cache() {
-    match Command::new("fc-cache").arg("-f").status() {
-        Ok(status) if status.success() => {}
-        Ok(_) => eprintln!("Warning: fc-cache returned non-zero status."),
-        Err(_) => eprintln!("Warning: fc-cache not found. Install fontconfig or refresh cache manually."),
+// A plan with failures exits as the install would, so it works as a preflight check
+fn dry_run_exit(report: &InstallReport) {
+    if !report.failures.is_empty() {
+        std::process::exit(batch_exit_code(&report.failures));
     }
 }
 
-fn user_fonts_base() -> PathBuf {
-    if let Some(xdg) = env::var_os("XDG_DATA_HOME") {
-        PathBuf::from(xdg).join("fonts")
-    } else if let Some(home) = env::var_os("HOME") {
-        PathBuf::from(home).join(".local/share/fonts")
-    } else {
-        PathBuf::from(".local/share/fonts")
+fn do_uninstall(installer: &Installer, query: &str, verbosity: Verbosity, format: OutputFormat) -> Result<()> {
+    let report = installer.uninstall(query)?;
+    match format {
+        OutputFormat::Text => {
+            if verbosity != Verbosity::Quiet {
+                for path in &report.removed {
+                    println!("Removed {}", path.display());
+                }
+            }
+            for warning in &report.warnings {
+                eprintln!("Warning: {warning}");
+            }
+            print_cache(&report.cache, verbosity);
+        }
+        OutputFormat::Json => output::document(&json!({
+            "scope": installer.scope().name(),
+            "removed": report.removed,
+            "warnings": report.warnings,
+            "cache_refresh": output::cache(&report.cache),
+        })),
+        OutputFormat::Ndjson => {
+            for path in &report.removed {
+                output::record("removed", json!({ "path": path }));
+            }
+            for warning in &report.warnings {
+                output::record("warning", json!({ "message": warning }));
+            }
+            output::record("summary", json!({
+                "scope": installer.scope().name(),
+                "removed": report.removed.len(),
+                "cache_refresh": output::cache(&report.cache),
+            }));
+        }
+    }
+    match report.cache {
+        // Already part of the machine-readable report
+        CacheStatus::Failed(e) if format != OutputFormat::Text => std::process::exit(e.exit_code()),
+        CacheStatus::Failed(e) => Err(e),
+        _ => Ok(()),
     }
 }
 
-fn is_perm_denied(e: &io::Error) -> bool {
-    e.kind() == io::ErrorKind::PermissionDenied || e.raw_os_error() == Some(13)
+fn print_entry(entry: &manifest::Entry) {
+    println!("  installed from: {}", entry.source.display());
+    println!("  installed at:   {} ({} scope)", manifest::format_timestamp(entry.installed_at), entry.scope.name());
+    println!("  sha256:         {}", entry.sha256);
 }
 
-fn escalate_and_reexec() -> io::Result<()> {
-    // Prevent loops if we’re already elevated
-    if env::var_os("INSTALL_FONT_ELEVATED").is_some() {
-        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied even after sudo retry"));
+// Long strings (licenses, descriptions) are cut to their start on one line
+// unless asked for in full
+fn excerpt(text: &str, verbosity: Verbosity) -> String {
+    if verbosity == Verbosity::Verbose {
+        return text.to_string();
+    }
+    let line = text.lines().find(|line| !line.trim().is_empty()).unwrap_or("").trim();
+    match line.char_indices().nth(72) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None if line.len() < text.trim().len() => format!("{line} …"),
+        None => line.to_string(),
     }
+}
 
-    let exe = env::current_exe()?;
-    let args: Vec<String> = env::args().skip(1).collect();
+fn print_font_info(info: &FontInfo, verbosity: Verbosity) {
+    println!("{}", info.path.display());
+    println!("  format:         {} ({} bytes)", info.kind.name(), info.size);
+    for (i, face) in info.faces.iter().enumerate() {
+        if info.faces.len() > 1 {
+            println!("  face {i}:");
+        }
+        let field = |label: &str, value: Option<&str>| {
+            if let Some(value) = value {
+                println!("  {:<15} {value}", format!("{label}:"));
+            }
+        };
+        field("family", face.names.family.as_deref());
+        field("style", face.names.subfamily.as_deref());
+        field("postscript", face.names.postscript.as_deref());
+        field("version", face.names.version.as_deref());
+        field("designer", face.designer.as_deref());
+        field("manufacturer", face.manufacturer.as_deref());
+        field("vendor id", face.vendor_id.as_deref());
+        field("license", face.license.as_deref().map(|license| excerpt(license, verbosity)).as_deref());
+        field("license url", face.license_url.as_deref());
+        if let (Some(weight), Some(name)) = (face.weight, face.weight_name()) {
+            field("weight", Some(&format!("{weight} ({name})")));
+        }
+        if let Some(width) = face.width {
+            field("width", Some(&format!("{width} ({})", face.width_name().unwrap_or("invalid"))));
+        }
+        if face.records.is_empty() {
+            // Legacy fonts carry nothing beyond their names
+            continue;
+        }
+        field("italic", Some(if face.italic { "yes" } else { "no" }));
+        field("glyphs", face.glyphs.map(|n| n.to_string()).as_deref());
+        if face.fs_type.is_some() {
+            field("embedding", Some(&face.embedding().join(", ")));
+        }
+        for axis in &face.axes {
+            let name = axis.name.as_deref().map(|name| format!(" ({name})")).unwrap_or_default();
+            field("axis", Some(&format!("{} {}..{}..{}{name}", axis.tag, axis.min, axis.default, axis.max)));
+        }
+        if !face.scripts.is_empty() {
+            let scripts = face.scripts.iter()
+                .map(|tag| match fontize::script_name(tag) {
+                    Some(name) => format!("{tag} ({name})"),
+                    None => tag.clone(),
+                })
+                .collect::<Vec<_>>();
+            field("scripts", Some(&scripts.join(", ")));
+        }
+        if !face.unicode_ranges.is_empty() {
+            field("unicode ranges", Some(&face.unicode_ranges.join(", ")));
+        }
+        println!("  name records:");
+        for record in &face.records {
+            println!(
+                "    {:>3} {:<22} {}/{} {:<8} {}",
+                record.name_id,
+                record.label(),
+                record.platform,
+                record.encoding,
+                record.language,
+                excerpt(&record.value, verbosity)
+            );
+        }
+    }
+}
 
-    eprintln!("Permission denied. Retrying with sudo… (you may be prompted for your password)");
-    let status = Command::new("sudo")
-        .env("INSTALL_FONT_ELEVATED", "1")
-        .arg(exe)
-        .args(&args)
-        .status();
+fn do_list(layout: &Layout, scope: Scope, only: Option<Scope>, args: &ListArgs, format: OutputFormat) -> Result<()> {
+    let filter = ListFilter { family: args.family.clone(), kind: args.format, scope: only };
+    let found = found(fontize::list(layout, scope, &filter)?);
+    match format {
+        OutputFormat::Text => {
+            for font in &found {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    font.names.family.as_deref().unwrap_or(""),
+                    font.names.subfamily.as_deref().unwrap_or(""),
+                    font.kind.name(),
+                    font.scope.name(),
+                    if font.managed { "managed" } else { "unmanaged" },
+                    font.path.display()
+                );
+            }
+        }
+        OutputFormat::Json => output::document(&found.iter().map(output::found).collect()),
+        OutputFormat::Ndjson => found.iter().for_each(|font| output::record("font", output::found(font))),
+    }
+    Ok(())
+}
 
-    match status {
-        Ok(s) => std::process::exit(s.code().unwrap_or(1)),
-        Err(e) => Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("Failed to execute sudo: {e}")
-        )),
+// The fonts the manifest of `scope` records, warning about lines it could not read
+fn managed(layout: &Layout, scope: Scope) -> Result<Vec<manifest::Entry>> {
+    let path = layout.manifest(scope);
+    let manifest = manifest::load_from(&path)?;
+    for line in manifest.skipped {
+        warn_skipped(&path, line);
     }
+    Ok(manifest.entries)
 }
 
-fn do_install(user_mode: bool, src_path: &Path) -> io::Result<()> {
-    let kind = detect_kind(src_path)?;
+// The fonts `list` or `search` found, warning about manifest lines they could not read
+fn found(installed: Installed) -> Vec<Found> {
+    for (path, line) in &installed.skipped {
+        warn_skipped(path, *line);
+    }
+    installed.fonts
+}
 
-    let base_dir = if user_mode {
-        user_fonts_base()
-    } else {
-        PathBuf::from("/usr/share/fonts")
-    };
+fn warn_skipped(manifest: &Path, line: usize) {
+    eprintln!("Warning: skipping malformed line {line} in {}", manifest.display());
+}
 
-    let subdir = match kind {
-        FontKind::Otf => "OTF",
-        FontKind::Ttf => "TTF",
-    };
-    let dest_dir = base_dir.join(subdir);
+fn do_info(layout: &Layout, scope: Scope, query: &str, verbosity: Verbosity, format: OutputFormat) -> Result<()> {
+    let bases: Vec<PathBuf> = scopes(scope).into_iter().map(|scope| layout.base(scope)).collect();
+    let target = query_path(query, &bases);
+    let mut fonts = Vec::new();
+    for scope in scopes(scope) {
+        for entry in managed(layout, scope)? {
+            let path = layout.staged(&entry.destination);
+            let hit = match &target {
+                Some(target) => &path == target,
+                None => matches_font(&path, query),
+            };
+            if hit {
+                fonts.push((path, Some(entry)));
+            }
+        }
+    }
+    // A font file fontize did not install, however it was named
+    if let (Some(file), true) = (target.or_else(|| fs::canonicalize(query).ok()), fonts.is_empty()) {
+        fonts.push((file, None));
+    }
+    if fonts.is_empty() {
+        return Err(Error::NotFound(format!("No font file or managed font matches {query}")));
+    }
 
-    fs::create_dir_all(&dest_dir)?;                      // may hit EACCES
-    let file_name = src_path.file_name()
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid source filename"))?;
-    let dest_path = unique_path(dest_dir.join(file_name));
+    let mut inspected = Vec::new();
+    for (path, entry) in fonts {
+        match fontize::inspect(&path) {
+            Ok(info) => inspected.push((path, Ok(info), entry)),
+            // The manifest still has something to say about a managed font
+            // that is gone or damaged
+            Err(e) if entry.is_some() => inspected.push((path, Err(e), entry)),
+            Err(e) => return Err(e),
+        }
+    }
+    match format {
+        OutputFormat::Text => {
+            for (path, info, entry) in &inspected {
+                match info {
+                    Ok(info) => print_font_info(info, verbosity),
+                    Err(e) => println!("{}\n  error:          {e}", path.display()),
+                }
+                if let Some(entry) = entry {
+                    print_entry(entry);
+                }
+            }
+        }
+        OutputFormat::Json => output::document(
+            &inspected.iter().map(|(path, info, entry)| output::info(path, info.as_ref(), entry.as_ref())).collect(),
+        ),
+        OutputFormat::Ndjson => {
+            for (path, info, entry) in &inspected {
+                output::record("font", output::info(path, info.as_ref(), entry.as_ref()));
+            }
+        }
+    }
+    Ok(())
+}
 
-    move_across_fs(src_path, &dest_path)?;               // may hit EACCES
-    set_permissions644(&dest_path)?;                     // may hit EACCES
+fn do_verify(layout: &Layout, scope: Scope, format: OutputFormat) -> Result<()> {
+    let mut results = Vec::new();
+    for scope in scopes(scope) {
+        for entry in managed(layout, scope)? {
+            let status = match manifest::sha256_file(&layout.staged(&entry.destination)) {
+                Ok(sha256) if sha256 == entry.sha256 => "OK",
+                Ok(_) => "MODIFIED",
+                Err(e) if e.kind() == io::ErrorKind::NotFound => "MISSING",
+                Err(_) => "UNREADABLE",
+            };
+            match format {
+                OutputFormat::Text => println!("{status}\t{}", entry.destination.display()),
+                OutputFormat::Json => {}
+                OutputFormat::Ndjson => output::record("font", output::verified(&entry, status)),
+            }
+            results.push((entry, status));
+        }
+    }
+    let failures = results.iter().filter(|(_, status)| *status != "OK").count();
+    match format {
+        OutputFormat::Text if failures > 0 => eprintln!("{failures} managed font(s) failed verification"),
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            output::document(&results.iter().map(|(entry, status)| output::verified(entry, status)).collect())
+        }
+        OutputFormat::Ndjson => output::record("summary", json!({ "checked": results.len(), "failed": failures })),
+    }
+    if failures > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-    println!("Installed {} -> {}", src_path.display(), dest_path.display());
-    refresh_font_cache();                                // not critical if it fails
+fn do_search(layout: &Layout, scope: Scope, query: &str, verbosity: Verbosity, format: OutputFormat) -> Result<()> {
+    let found = found(fontize::search(layout, scope, query)?);
+    if found.is_empty() {
+        return Err(Error::NotFound(format!("No installed font matches {query}")));
+    }
+    match format {
+        OutputFormat::Text => {
+            for font in &found {
+                let family = font.names.family.as_deref().unwrap_or("");
+                let subfamily = font.names.subfamily.as_deref().unwrap_or("");
+                if verbosity == Verbosity::Verbose {
+                    println!("{}\t{family}\t{subfamily}\t{}", font.scope.name(), font.path.display());
+                } else {
+                    println!("{family}\t{subfamily}\t{}", font.path.display());
+                }
+            }
+        }
+        OutputFormat::Json => output::document(&found.iter().map(output::found).collect()),
+        OutputFormat::Ndjson => found.iter().for_each(|font| output::record("font", output::found(font))),
+    }
     Ok(())
 }
 
-fn main() -> io::Result<()> {
-    let args = env::args().skip(1).collect::<Vec<_>>();
-    if args.is_empty() || args.len() > 2 {
-        eprintln!("Usage: install_font <path-to-font> [--user]");
-        eprintln!("  --user   Install to ~/.local/share/fonts (XDG) instead of /usr/share/fonts");
-        std::process::exit(2);
+fn do_cache(layout: &Layout, scope: Scope, command: &CacheCommand, verbosity: Verbosity, format: OutputFormat) -> Result<()> {
+    let (dirs, force) = match command {
+        CacheCommand::Refresh { dirs } => (dirs, false),
+        CacheCommand::Rebuild { dirs } => (dirs, true),
+    };
+    let refresh = match &layout.root {
+        // Directories are named as the staged system will see them
+        Some(root) => {
+            let dirs: Vec<PathBuf> = dirs.iter().map(|dir| layout.staged(dir)).collect();
+            fontize::refresh_staged_cache(root, scope, &dirs, force)?
+        }
+        None => fontize::refresh_font_cache(scope, dirs, force)?,
+    };
+    match format {
+        OutputFormat::Text if verbosity != Verbosity::Quiet || refresh.fallback => print_refresh(&refresh),
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            output::document(&json!({ "cache_refresh": output::cache(&CacheStatus::Refreshed(refresh)) }))
+        }
+        OutputFormat::Ndjson => output::record("cache_refresh", output::cache(&CacheStatus::Refreshed(refresh))),
     }
+    Ok(())
+}
 
-    let user_mode = args.iter().any(|a| a == "--user");
-    let src_path = PathBuf::from(&args[0]);
+fn do_config(config: &Config, command: &ConfigCommand, format: OutputFormat) -> Result<()> {
+    match command {
+        ConfigCommand::Show => {
+            let settings = config.settings();
+            match format {
+                OutputFormat::Text => {
+                    for (key, value, origin) in &settings {
+                        println!("{key:<14} {value:<32} {origin}");
+                    }
+                }
+                OutputFormat::Json => output::document(&json!(settings.iter()
+                    .map(|(key, value, origin)| Config::setting_json(key, value, origin))
+                    .collect::<Vec<_>>())),
+                OutputFormat::Ndjson => {
+                    for (key, value, origin) in &settings {
+                        output::record("setting", Config::setting_json(key, value, origin));
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
 
-    if !src_path.exists() || !src_path.is_file() {
-        eprintln!("Error: source file does not exist or is not a file: {}", src_path.display());
-        std::process::exit(1);
+fn fail(format: OutputFormat, e: Error) -> ! {
+    match format {
+        OutputFormat::Text => eprintln!("Error: {e}"),
+        OutputFormat::Json => output::document(&output::error(&e)),
+        OutputFormat::Ndjson => output::record("error", output::error(&e)),
+    }
+    std::process::exit(e.exit_code());
+}
+
+fn main() {
+    let cli = Cli::parse();
+    // Root's own configuration alone says where the helper may write
+    if let Command::Helper = cli.command {
+        let result = Config::system().and_then(|config| {
+            fontize::serve_helper(&config.system_base.value, &mut io::stdin().lock(), &mut io::stdout().lock())
+        });
+        if let Err(e) = result {
+            fail(cli.output, e);
+        }
+        return;
     }
+    let verbosity = if cli.quiet {
+        Verbosity::Quiet
+    } else if cli.verbose {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    };
+    let mut config = Config::load().unwrap_or_else(|e| fail(cli.output, e));
+    config.apply_flags(&cli);
+    let root = cli.root.clone().or_else(|| env::var_os("DESTDIR").filter(|dir| !dir.is_empty()).map(PathBuf::from));
+    let staged = root.is_some();
+    let layout = config.layout(root);
+    let interactive = !cli.non_interactive;
+    // A staging tree is ours to write; root rights would only touch the host
+    let mut backend = if staged { Backend::None } else { config.escalate.value };
+    let changes = matches!(cli.command, Command::Install(_) | Command::Uninstall { .. } | Command::Cache(_));
+    let scope = match config.scope.value {
+        ScopeChoice::User => Scope::User,
+        ScopeChoice::System => Scope::System,
+        // Looking needs no rights, so everything is looked at, and staged
+        // trees are packaged for the whole system
+        ScopeChoice::Auto if !changes || staged => Scope::System,
+        ScopeChoice::Auto => {
+            let (scope, found, reason) = auto_scope(backend);
+            backend = found;
+            if verbosity != Verbosity::Quiet {
+                eprintln!("Using the {} scope ({reason})", scope.name());
+            }
+            scope
+        }
+    };
+    // The host's cache has nothing to do with a staged tree
+    let cache_refresh = if staged && !cli.sysroot_cache { CacheRefresh::Never } else { config.cache_refresh.value };
 
-    match do_install(user_mode, &src_path) {
-        Ok(()) => Ok(()),
-        Err(e) if is_perm_denied(&e) && !user_mode => {
-            // Auto-retry with sudo for system-wide installs
-            let _ = escalate_and_reexec()?;
-            Ok(()) // unreachable
+    let result = match &cli.command {
+        Command::Install(args) => {
+            let mut installer = Installer::new(scope)
+                .layout(layout)
+                .cache_refresh(cache_refresh)
+                .on_conflict(config.on_conflict.value)
+                .with_docs(args.with_docs)
+                .move_files(config.move_files.value)
+                .decompress(!args.no_decompress)
+                .split(args.split)
+                .force(args.force);
+            match args.dry_run {
+                Some(format) => do_dry_run(&installer, &args.paths, backend, interactive, format.unwrap_or(cli.output)),
+                None => {
+                    // Fonts are parsed here, unprivileged; only writing them
+                    // where we may not is left to a helper running as root
+                    let mut helper = None;
+                    if scope == Scope::System && backend.enabled() {
+                        match escalate::helper_command(backend, interactive) {
+                            Ok(command) => installer = installer.privileged_helper(command),
+                            Err(e) => helper = Some(e),
+                        }
+                    }
+                    do_install_batch(&installer, &args.paths, helper, verbosity, cli.output)
+                }
+            }
         }
-        Err(e) => Err(e),
+        Command::Uninstall { query } => {
+            // Matching parses fonts, so it happens here too; the helper
+            // only removes what we may not
+            let mut installer = Installer::new(scope).layout(layout).cache_refresh(cache_refresh);
+            let mut helper = None;
+            if scope == Scope::System && backend.enabled() {
+                match escalate::helper_command(backend, interactive) {
+                    Ok(command) => installer = installer.privileged_helper(command),
+                    Err(e) => helper = Some(e),
+                }
+            }
+            match (do_uninstall(&installer, query, verbosity, cli.output), helper) {
+                // Denied for want of the helper, which is the real news
+                (Err(Error::PermissionDenied(_)), Some(e)) => Err(e),
+                (result, _) => result,
+            }
+        }
+        // An explicit --scope (or --user, --system) lists that scope alone
+        Command::List(args) => {
+            let only = match cli.scope {
+                Some(ScopeChoice::User) => Some(Scope::User),
+                Some(ScopeChoice::System) => Some(Scope::System),
+                _ if cli.user => Some(Scope::User),
+                _ if cli.system => Some(Scope::System),
+                _ => None,
+            };
+            do_list(&layout, scope, only, args, cli.output)
+        }
+        Command::Info { query } => do_info(&layout, scope, query, verbosity, cli.output),
+        Command::Search { query } => do_search(&layout, scope, query, verbosity, cli.output),
+        Command::Verify => do_verify(&layout, scope, cli.output),
+        // Only the system cache when asked for; the user's covers every directory
+        Command::Cache(command) => {
+            let scope = if cli.system || cli.scope.is_some() { scope } else { Scope::User };
+            do_cache(&layout, scope, command, verbosity, cli.output)
+        }
+        Command::Config(command) => do_config(&config, command, cli.output),
+        Command::Completions { shell } => {
+            clap_complete::generate(*shell, &mut Cli::command(), "fontize", &mut io::stdout());
+            Ok(())
+        }
+        Command::Man => clap_mangen::Man::new(Cli::command()).render(&mut io::stdout()).map_err(Error::from),
+        Command::Helper => unreachable!("handled above"),
+    };
+
+    // Only cache reruns as root when denied: installs and uninstalls go
+    // through the helper, and rerunning a read-only command would parse
+    // fonts as root, or ask for a password just to look
+    let reruns = matches!(cli.command, Command::Cache(_));
+    let result = match result {
+        Err(Error::PermissionDenied(_)) if reruns && scope == Scope::System && backend.enabled() => {
+            escalate_and_reexec(backend, interactive, &env::args_os().skip(1).collect::<Vec<_>>())
+        }
+        result => result,
+    };
+    if let Err(e) = result {
+        fail(cli.output, e);
     }
 }
@@ -0,0 +1,245 @@
+// Ledger of every font fontize has installed, one tab-separated record per line.
+// User installs are tracked under XDG state, system installs under /var/lib;
+// staged installs keep theirs at the same place inside the staging tree.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::{FontKind, Scope};
+
+const HEADER: &str = "# fontize manifest v1: installed_at\tscope\tkind\tsha256\tsource\tdestination";
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub installed_at: u64,
+    pub scope: Scope,
+    pub kind: FontKind,
+    pub sha256: String,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+}
+
+impl Entry {
+    pub(crate) fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.installed_at,
+            self.scope.name(),
+            self.kind.name(),
+            self.sha256,
+            escape(&self.source.to_string_lossy()),
+            escape(&self.destination.to_string_lossy()),
+        )
+    }
+
+    pub(crate) fn from_line(line: &str) -> Option<Entry> {
+        let mut fields = line.split('\t');
+        let entry = Entry {
+            installed_at: fields.next()?.parse().ok()?,
+            scope: Scope::from_name(fields.next()?)?,
+            kind: FontKind::from_name(fields.next()?)?,
+            sha256: fields.next()?.to_string(),
+            source: PathBuf::from(unescape(fields.next()?)),
+            destination: PathBuf::from(unescape(fields.next()?)),
+        };
+        fields.next().is_none().then_some(entry)
+    }
+}
+
+pub fn manifest_path(scope: Scope) -> PathBuf {
+    match scope {
+        Scope::User => dirs::state_dir()
+            .unwrap_or_else(|| PathBuf::from(".local/state"))
+            .join("fontize/manifest.tsv"),
+        Scope::System => PathBuf::from("/var/lib/fontize/manifest.tsv"),
+    }
+}
+
+/// A manifest as read back.
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    pub entries: Vec<Entry>,
+    /// Numbers of the lines that could not be read, left out of `entries`.
+    pub skipped: Vec<usize>,
+}
+
+pub fn load(scope: Scope) -> io::Result<Manifest> {
+    load_from(&manifest_path(scope))
+}
+
+/// Reads the manifest at `path`, e.g. [`Layout::manifest`](crate::Layout::manifest).
+pub fn load_from(path: &Path) -> io::Result<Manifest> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Manifest::default()),
+        Err(e) => return Err(e),
+    };
+    let mut manifest = Manifest::default();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match Entry::from_line(&line) {
+            Some(entry) => manifest.entries.push(entry),
+            None => manifest.skipped.push(n + 1),
+        }
+    }
+    Ok(manifest)
+}
+
+pub(crate) fn save(path: &Path, entries: &[Entry]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Write next to the manifest and rename so readers never see a partial file
+    let tmp = path.with_extension("tsv.tmp");
+    {
+        let mut f = File::create(&tmp)?;
+        writeln!(f, "{HEADER}")?;
+        for entry in entries {
+            writeln!(f, "{}", entry.to_line())?;
+        }
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+/// Adds `entry` to the manifest at `path`, replacing any earlier record for
+/// the same destination.
+pub(crate) fn record(path: &Path, entry: Entry) -> io::Result<()> {
+    let mut entries = load_from(path)?.entries;
+    entries.retain(|e| e.destination != entry.destination);
+    entries.push(entry);
+    save(path, &entries)
+}
+
+/// Drops the records for `destinations`; a no-op if none of them are tracked.
+pub(crate) fn forget(path: &Path, destinations: &[PathBuf]) -> io::Result<()> {
+    let mut entries = load_from(path)?.entries;
+    let before = entries.len();
+    entries.retain(|e| !destinations.contains(&e.destination));
+    if entries.len() == before {
+        return Ok(());
+    }
+    save(path, &entries)
+}
+
+pub(crate) fn sha256_bytes(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut f = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Formats seconds since the epoch as an RFC 3339 UTC timestamp.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days (Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_round_trip_with_escapes() {
+        let entry = Entry {
+            installed_at: 1_700_000_000,
+            scope: Scope::System,
+            kind: FontKind::Woff2,
+            sha256: "ab".repeat(32),
+            source: PathBuf::from("/tmp/odd\tname\\with\nbreaks.woff2"),
+            destination: PathBuf::from("/usr/share/fonts/TTF/Open Sans/OpenSans-Italic.ttf"),
+        };
+        let line = entry.to_line();
+        assert_eq!(line.split('\t').count(), 6);
+        assert!(line.contains("/tmp/odd\\tname\\\\with\\nbreaks.woff2"), "{line}");
+
+        let back = Entry::from_line(&line).unwrap();
+        assert_eq!(back.installed_at, entry.installed_at);
+        assert_eq!(back.scope, entry.scope);
+        assert_eq!(back.kind, entry.kind);
+        assert_eq!(back.sha256, entry.sha256);
+        assert_eq!(back.source, entry.source);
+        assert_eq!(back.destination, entry.destination);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        assert!(Entry::from_line("").is_none());
+        assert!(Entry::from_line("x\tsystem\tttf\tab\t/a\t/b").is_none());
+        assert!(Entry::from_line("1\tglobal\tttf\tab\t/a\t/b").is_none());
+        assert!(Entry::from_line("1\tuser\tttf\tab\t/a").is_none());
+        assert!(Entry::from_line("1\tuser\tttf\tab\t/a\t/b\textra").is_none());
+    }
+
+    #[test]
+    fn timestamps_are_rfc3339_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(format_timestamp(4_102_444_799), "2099-12-31T23:59:59Z");
+    }
+}
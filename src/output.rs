@@ -0,0 +1,168 @@
+// Machine-readable renderings of what the commands did, shared by
+// `--output json` (one document per command) and `--output ndjson` (one
+// record per line, tagged with its "type", written as work completes).
+
+use std::path::Path;
+
+use serde_json::{Value, json};
+
+use fontize::{Action, CacheStatus, Error, FaceInfo, Failure, FontInfo, FontKind, FontReport, Found, manifest};
+
+pub fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Installed => "install",
+        Action::Replaced => "replace",
+        Action::Skipped => "skip",
+    }
+}
+
+pub fn font(font: &FontReport) -> Value {
+    json!({
+        "source": font.source,
+        "destination": font.destination,
+        "kind": font.kind.name(),
+        "converted_from": font.converted_from.map(FontKind::name),
+        "family": font.names.family,
+        "subfamily": font.names.subfamily,
+        "postscript_name": font.names.postscript,
+        "version": font.names.version,
+        "faces": font.faces.iter().map(|face| json!({
+            "family": face.family,
+            "subfamily": face.subfamily,
+            "postscript_name": face.postscript,
+        })).collect::<Vec<_>>(),
+        "metrics": font.metrics.iter().map(|(metrics, dest)| json!({
+            "source": metrics,
+            "destination": dest,
+        })).collect::<Vec<_>>(),
+        "sha256": font.sha256,
+        "action": action_name(font.action),
+        "note": font.note,
+        "warnings": font.warnings,
+    })
+}
+
+pub fn error(e: &Error) -> Value {
+    json!({
+        "error": e.to_string(),
+        "category": e.category(),
+        "exit_code": e.exit_code(),
+    })
+}
+
+pub fn failure(failure: &Failure) -> Value {
+    let mut value = error(&failure.error);
+    value["source"] = json!(failure.source);
+    value
+}
+
+pub fn doc(source: &std::path::Path, destination: &std::path::Path) -> Value {
+    json!({ "source": source, "destination": destination })
+}
+
+pub fn cache(status: &CacheStatus) -> Value {
+    match status {
+        CacheStatus::Skipped => json!({ "status": "skipped" }),
+        CacheStatus::Pending(dirs) => json!({ "status": "pending", "dirs": dirs }),
+        CacheStatus::Refreshed(refresh) => json!({
+            "status": if refresh.fallback { "marked_stale" } else { "refreshed" },
+            "scope": refresh.scope.name(),
+            "invoking_user": refresh.invoking_user,
+            "dirs": refresh.dirs,
+            "forced": refresh.forced,
+            "seconds": refresh.elapsed.as_secs_f64(),
+        }),
+        CacheStatus::Failed(e) => json!({ "status": "failed", "error": e.to_string() }),
+    }
+}
+
+pub fn entry(entry: &manifest::Entry) -> Value {
+    json!({
+        "installed_at": manifest::format_timestamp(entry.installed_at),
+        "scope": entry.scope.name(),
+        "kind": entry.kind.name(),
+        "sha256": entry.sha256,
+        "source": entry.source,
+        "destination": entry.destination,
+    })
+}
+
+fn face(face: &FaceInfo) -> Value {
+    json!({
+        "family": face.names.family,
+        "subfamily": face.names.subfamily,
+        "postscript_name": face.names.postscript,
+        "version": face.names.version,
+        "designer": face.designer,
+        "manufacturer": face.manufacturer,
+        "vendor_id": face.vendor_id,
+        "license": face.license,
+        "license_url": face.license_url,
+        "weight": face.weight,
+        "weight_name": face.weight_name(),
+        "width": face.width,
+        "width_name": face.width_name(),
+        "italic": face.italic,
+        "glyphs": face.glyphs,
+        "fs_type": face.fs_type,
+        "embedding": face.embedding(),
+        "axes": face.axes.iter().map(|axis| json!({
+            "tag": axis.tag,
+            "name": axis.name,
+            "min": axis.min,
+            "default": axis.default,
+            "max": axis.max,
+        })).collect::<Vec<_>>(),
+        "scripts": face.scripts,
+        "unicode_ranges": face.unicode_ranges,
+        "names": face.records.iter().map(|record| json!({
+            "name_id": record.name_id,
+            "label": record.label(),
+            "platform": record.platform,
+            "encoding": record.encoding,
+            "language": record.language,
+            "value": record.value,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// What `info` found out about a font file, and the manifest record if
+/// fontize installed it.
+pub fn info(path: &Path, info: Result<&FontInfo, &Error>, entry: Option<&manifest::Entry>) -> Value {
+    json!({
+        "path": path,
+        "kind": info.ok().map(|info| info.kind.name()),
+        "size": info.ok().map(|info| info.size),
+        "faces": info.ok().map(|info| info.faces.iter().map(face).collect::<Vec<_>>()),
+        "error": info.err().map(error),
+        "installed": entry.map(self::entry),
+    })
+}
+
+pub fn found(found: &Found) -> Value {
+    json!({
+        "path": found.path,
+        "scope": found.scope.name(),
+        "kind": found.kind.name(),
+        "managed": found.managed,
+        "family": found.names.family,
+        "subfamily": found.names.subfamily,
+        "postscript_name": found.names.postscript,
+        "version": found.names.version,
+    })
+}
+
+/// Writes one NDJSON record.
+pub fn record(kind: &str, mut value: Value) {
+    value["type"] = json!(kind);
+    println!("{value}");
+}
+
+pub fn verified(entry: &manifest::Entry, status: &str) -> Value {
+    json!({ "status": status.to_lowercase(), "destination": entry.destination })
+}
+
+/// Writes a whole JSON document.
+pub fn document(value: &Value) {
+    println!("{value:#}");
+}
@@ -0,0 +1,101 @@
+// Finding installed fonts, whether or not fontize installed them.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::Result;
+use crate::files::files_under;
+use crate::install::normalize_name;
+use crate::kind::{FontKind, detect_kind, has_font_extension, read_names};
+use crate::layout::{Layout, Scope, scopes};
+use crate::manifest;
+use crate::sfnt::Names;
+
+#[derive(Debug)]
+pub struct Found {
+    pub path: PathBuf,
+    pub scope: Scope,
+    pub kind: FontKind,
+    pub names: Names,
+    /// Whether the manifest records fontize installing it.
+    pub managed: bool,
+}
+
+/// Narrows down [`list`]; fields left `None` match every font.
+#[derive(Debug, Clone, Default)]
+pub struct ListFilter {
+    /// Family name, ignoring case, spaces, dashes and underscores.
+    pub family: Option<String>,
+    pub kind: Option<FontKind>,
+    pub scope: Option<Scope>,
+}
+
+/// What [`list`] and [`search`] found.
+#[derive(Debug, Default)]
+pub struct Installed {
+    pub fonts: Vec<Found>,
+    /// Manifest and number of every line that could not be read; fonts it
+    /// recorded show up as unmanaged.
+    pub skipped: Vec<(PathBuf, usize)>,
+}
+
+// Every font file in the font directories of `scope`, each listed once even
+// when directories overlap through symlinks
+fn installed(layout: &Layout, scope: Scope) -> Result<Installed> {
+    let mut managed = HashSet::new();
+    let mut skipped = Vec::new();
+    for scope in scopes(scope) {
+        let path = layout.manifest(scope);
+        let manifest = manifest::load_from(&path)?;
+        managed.extend(manifest.entries.into_iter().map(|entry| layout.staged(&entry.destination)));
+        skipped.extend(manifest.skipped.into_iter().map(|line| (path.clone(), line)));
+    }
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for scope in scopes(scope) {
+        for dir in layout.font_dirs(scope) {
+            let mut files = Vec::new();
+            files_under(&dir, &mut files)?;
+            files.sort();
+            for path in files.into_iter().filter(|f| has_font_extension(f)) {
+                if !seen.insert(fs::canonicalize(&path).unwrap_or_else(|_| path.clone())) {
+                    continue;
+                }
+                let Ok(kind) = detect_kind(&path) else { continue };
+                let names = read_names(&path).unwrap_or_default();
+                let managed = managed.contains(&path);
+                found.push(Found { path, scope, kind, names, managed });
+            }
+        }
+    }
+    Ok(Installed { fonts: found, skipped })
+}
+
+/// Installed fonts whose file name, family or PostScript name contains
+/// `query`, ignoring case, spaces, dashes and underscores.
+pub fn search(layout: &Layout, scope: Scope, query: &str) -> Result<Installed> {
+    let query = normalize_name(query);
+    let mut found = installed(layout, scope)?;
+    found.fonts.retain(|font| {
+        let stem = font.path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        [Some(&stem), font.names.family.as_ref(), font.names.postscript.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|name| normalize_name(name).contains(&query))
+    });
+    Ok(found)
+}
+
+/// Every installed font in `scope` that passes `filter`, in the user's
+/// directories first, then fontize's base before the other directories.
+pub fn list(layout: &Layout, scope: Scope, filter: &ListFilter) -> Result<Installed> {
+    let family = filter.family.as_deref().map(normalize_name);
+    let mut found = installed(layout, scope)?;
+    found.fonts.retain(|font| {
+        family.as_ref().is_none_or(|family| font.names.family.as_deref().map(normalize_name).as_ref() == Some(family))
+            && filter.kind.is_none_or(|kind| font.kind == kind)
+            && filter.scope.is_none_or(|scope| font.scope == scope)
+    });
+    Ok(found)
+}
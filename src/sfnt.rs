@@ -0,0 +1,233 @@
+// Minimal access to sfnt (TrueType/OpenType) files: the table directory, the
+// naming table, collections, and writing tables back out as a standalone font.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TableRecord {
+    pub tag: [u8; 4],
+    pub checksum: u32,
+    pub offset: u32,
+    pub length: u32,
+}
+
+pub struct Font<'a> {
+    data: &'a [u8],
+    pub sfnt_version: u32,
+    pub tables: Vec<TableRecord>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Names {
+    pub family: Option<String>,
+    pub subfamily: Option<String>,
+    pub postscript: Option<String>,
+    pub version: Option<String>,
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl<'a> Font<'a> {
+    /// Parses a single font, or the first face of a collection.
+    pub fn parse(data: &'a [u8]) -> io::Result<Font<'a>> {
+        if data.starts_with(b"ttcf") {
+            let first = read_u32(data, 12).ok_or_else(|| invalid("Truncated collection header"))?;
+            return Font::parse_at(data, first as usize);
+        }
+        Font::parse_at(data, 0)
+    }
+
+    /// Parses the table directory starting at `offset`; table offsets are
+    /// relative to the start of `data`, as they are inside collections.
+    pub fn parse_at(data: &'a [u8], offset: usize) -> io::Result<Font<'a>> {
+        let sfnt_version = read_u32(data, offset).ok_or_else(|| invalid("Truncated sfnt header"))?;
+        let num_tables = read_u16(data, offset + 4).ok_or_else(|| invalid("Truncated sfnt header"))?;
+        let mut tables = Vec::with_capacity(num_tables as usize);
+        for i in 0..num_tables as usize {
+            let rec = offset + 12 + i * 16;
+            let (Some(tag), Some(checksum), Some(table_offset), Some(length)) = (
+                data.get(rec..rec + 4),
+                read_u32(data, rec + 4),
+                read_u32(data, rec + 8),
+                read_u32(data, rec + 12),
+            ) else {
+                return Err(invalid("Truncated table directory"));
+            };
+            tables.push(TableRecord { tag: [tag[0], tag[1], tag[2], tag[3]], checksum, offset: table_offset, length });
+        }
+        Ok(Font { data, sfnt_version, tables })
+    }
+
+    pub fn table(&self, tag: &[u8; 4]) -> Option<&'a [u8]> {
+        let rec = self.tables.iter().find(|t| &t.tag == tag)?;
+        let start = rec.offset as usize;
+        self.data.get(start..start.checked_add(rec.length as usize)?)
+    }
+
+    /// Looks up name `id`, preferring Windows English, then any Unicode
+    /// encoding, then Mac Roman.
+    pub fn name(&self, id: u16) -> Option<String> {
+        let table = self.table(b"name")?;
+        let count = read_u16(table, 2)? as usize;
+        let storage = read_u16(table, 4)? as usize;
+
+        let mut best: Option<(u8, String)> = None;
+        for i in 0..count {
+            let rec = 6 + i * 12;
+            let (Some(platform), Some(encoding), Some(language), Some(name_id), Some(length), Some(offset)) = (
+                read_u16(table, rec),
+                read_u16(table, rec + 2),
+                read_u16(table, rec + 4),
+                read_u16(table, rec + 6),
+                read_u16(table, rec + 8),
+                read_u16(table, rec + 10),
+            ) else {
+                break;
+            };
+            if name_id != id {
+                continue;
+            }
+            let rank = match (platform, encoding, language) {
+                (3, 1 | 10, 0x409) => 0,
+                (3, 1 | 10, _) | (0, _, _) => 1,
+                (1, 0, _) => 2,
+                _ => continue,
+            };
+            if best.as_ref().is_some_and(|(r, _)| *r <= rank) {
+                continue;
+            }
+            let start = storage + offset as usize;
+            let Some(bytes) = table.get(start..start + length as usize) else { continue };
+            let text = decode_name(platform, bytes);
+            if !text.trim().is_empty() {
+                best = Some((rank, text.trim().to_string()));
+            }
+        }
+        best.map(|(_, text)| text)
+    }
+
+    pub fn names(&self) -> Names {
+        Names {
+            // Typographic names group every weight under one family
+            family: self.name(16).or_else(|| self.name(1)),
+            subfamily: self.name(17).or_else(|| self.name(2)),
+            postscript: self.name(6),
+            version: self.name(5),
+        }
+    }
+}
+
+/// Offsets of every face in a TrueType/OpenType collection.
+pub fn collection_offsets(data: &[u8]) -> io::Result<Vec<u32>> {
+    if !data.starts_with(b"ttcf") {
+        return Err(invalid("Not a font collection"));
+    }
+    let count = read_u32(data, 8).ok_or_else(|| invalid("Truncated collection header"))?;
+    (0..count as usize)
+        .map(|i| read_u32(data, 12 + i * 4).ok_or_else(|| invalid("Truncated collection header")))
+        .collect()
+}
+
+/// Names of every face in a collection, in offset-table order.
+pub fn collection_faces(data: &[u8]) -> io::Result<Vec<Names>> {
+    collection_offsets(data)?
+        .into_iter()
+        .map(|offset| Ok(Font::parse_at(data, offset as usize)?.names()))
+        .collect()
+}
+
+/// Copies one face of a collection out into a standalone font.
+pub fn extract_face(data: &[u8], offset: u32) -> io::Result<Vec<u8>> {
+    let font = Font::parse_at(data, offset as usize)?;
+    let tables = font.tables.iter()
+        .map(|rec| {
+            let table = font.table(&rec.tag).ok_or_else(|| invalid("Table data out of bounds"))?;
+            Ok(Table { tag: rec.tag, data: table.to_vec() })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(build_sfnt(font.sfnt_version, tables))
+}
+
+pub fn decode_name(platform: u16, bytes: &[u8]) -> String {
+    if platform == 1 {
+        // Mac Roman; the ASCII half covers the names fonts actually use
+        return bytes.iter().map(|&b| if b < 0x80 { b as char } else { '\u{fffd}' }).collect();
+    }
+    let units = bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]]));
+    char::decode_utf16(units).map(|c| c.unwrap_or('\u{fffd}')).collect()
+}
+
+pub fn read_names(path: &Path) -> io::Result<Names> {
+    let data = fs::read(path)?;
+    Ok(Font::parse(&data)?.names())
+}
+
+pub struct Table {
+    pub tag: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+pub fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+// Lays the tables out as an sfnt. Table offsets differ from whatever the
+// original file had, so head's checkSumAdjustment is always recomputed.
+pub fn build_sfnt(flavor: u32, mut tables: Vec<Table>) -> Vec<u8> {
+    tables.sort_by_key(|t| t.tag);
+    let num_tables = tables.len() as u16;
+    let entry_selector = if num_tables == 0 { 0 } else { 15 - num_tables.leading_zeros() as u16 };
+    let search_range = (1u16 << entry_selector) * 16;
+
+    if let Some(head) = tables.iter_mut().find(|t| &t.tag == b"head") && head.data.len() >= 12 {
+        head.data[8..12].fill(0);
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&flavor.to_be_bytes());
+    out.extend_from_slice(&num_tables.to_be_bytes());
+    out.extend_from_slice(&search_range.to_be_bytes());
+    out.extend_from_slice(&entry_selector.to_be_bytes());
+    out.extend_from_slice(&(num_tables * 16 - search_range).to_be_bytes());
+
+    let mut offset = 12 + tables.len() * 16;
+    for table in &tables {
+        out.extend_from_slice(&table.tag);
+        out.extend_from_slice(&checksum(&table.data).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(table.data.len() as u32).to_be_bytes());
+        offset += table.data.len().next_multiple_of(4);
+    }
+    let mut head_offset = None;
+    for table in &tables {
+        if &table.tag == b"head" {
+            head_offset = Some(out.len());
+        }
+        out.extend_from_slice(&table.data);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+
+    if let Some(head) = head_offset && out.len() >= head + 12 {
+        let adjustment = 0xb1b0_afbau32.wrapping_sub(checksum(&out));
+        out[head + 8..head + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    out
+}
@@ -0,0 +1,324 @@
+// Structural checks run before a font is installed, so that truncated
+// downloads and renamed HTML pages are caught here rather than breaking
+// fontconfig scans later. Errors make a font uninstallable; warnings are
+// worth reporting but common enough in shipped fonts to let through.
+
+use crate::sfnt::{self, Font, checksum, read_u16, read_u32};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity { Error, Warning }
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+struct Report(Vec<Diagnostic>);
+
+impl Report {
+    fn error(&mut self, message: impl Into<String>) {
+        self.0.push(Diagnostic { severity: Severity::Error, message: message.into() });
+    }
+
+    fn warn(&mut self, message: impl Into<String>) {
+        self.0.push(Diagnostic { severity: Severity::Warning, message: message.into() });
+    }
+}
+
+const HEAD_MAGIC: u32 = 0x5f0f_3cf5;
+
+/// Checks a font or collection; `data` must already be a plain sfnt (web
+/// fonts are validated after decoding).
+pub fn check(data: &[u8]) -> Vec<Diagnostic> {
+    let mut report = Report(Vec::new());
+
+    if let Some(what) = sniff_non_font(data) {
+        report.error(format!("File is {what}, not a font"));
+        return report.0;
+    }
+
+    if data.starts_with(b"ttcf") {
+        match sfnt::collection_offsets(data) {
+            Ok(offsets) if offsets.is_empty() => report.error("Collection contains no fonts"),
+            Ok(offsets) => {
+                for (i, offset) in offsets.into_iter().enumerate() {
+                    let mut face = Report(Vec::new());
+                    check_face(data, offset as usize, false, &mut face);
+                    for d in face.0 {
+                        report.0.push(Diagnostic { message: format!("face {i}: {}", d.message), ..d });
+                    }
+                }
+            }
+            Err(e) => report.error(e.to_string()),
+        }
+    } else {
+        check_face(data, 0, true, &mut report);
+    }
+    report.0
+}
+
+fn sniff_non_font(data: &[u8]) -> Option<&'static str> {
+    let head = data.iter().skip_while(|b| b.is_ascii_whitespace()).take(64).copied().collect::<Vec<_>>();
+    let lower = String::from_utf8_lossy(&head).to_lowercase();
+    if lower.starts_with("<!doctype html") || lower.starts_with("<html") || lower.starts_with("<head") {
+        Some("an HTML page")
+    } else if lower.starts_with("<?xml") || lower.starts_with("<svg") {
+        Some("an XML/SVG document")
+    } else if lower.starts_with('{') || lower.starts_with('[') {
+        Some("a JSON document")
+    } else if data.is_empty() {
+        Some("empty")
+    } else {
+        None
+    }
+}
+
+fn check_face(data: &[u8], offset: usize, whole_file: bool, report: &mut Report) {
+    let font = match Font::parse_at(data, offset) {
+        Ok(font) => font,
+        Err(e) => return report.error(e.to_string()),
+    };
+    match font.sfnt_version {
+        0x0001_0000 | 0x4f54_544f /* OTTO */ => {}
+        0x7472_7565 /* true */ => report.warn("Legacy Apple 'true' sfnt version"),
+        v => return report.error(format!("Unknown sfnt version 0x{v:08x}")),
+    }
+    if font.tables.is_empty() {
+        return report.error("Font has no tables");
+    }
+
+    let mut out_of_bounds = Vec::new();
+    for (i, rec) in font.tables.iter().enumerate() {
+        let tag = String::from_utf8_lossy(&rec.tag);
+        let end = rec.offset as u64 + rec.length as u64;
+        if end > data.len() as u64 {
+            out_of_bounds.push(format!("'{tag}'"));
+            continue;
+        }
+        if rec.offset % 4 != 0 {
+            report.warn(format!("Table '{tag}' is not 4-byte aligned"));
+        }
+        if i > 0 && font.tables[i - 1].tag >= rec.tag {
+            report.warn(format!("Table directory is not sorted by tag at '{tag}'"));
+        }
+        let table = &data[rec.offset as usize..end as usize];
+        let actual = if &rec.tag == b"head" && table.len() >= 12 {
+            // checkSumAdjustment is excluded from head's own checksum
+            let mut head = table.to_vec();
+            head[8..12].fill(0);
+            checksum(&head)
+        } else {
+            checksum(table)
+        };
+        if actual != rec.checksum {
+            report.warn(format!("Checksum mismatch in table '{tag}' (stored 0x{:08x}, computed 0x{actual:08x})", rec.checksum));
+        }
+    }
+    if !out_of_bounds.is_empty() {
+        return report.error(format!(
+            "File is truncated: table(s) {} extend past its end ({} bytes)",
+            out_of_bounds.join(", "),
+            data.len()
+        ));
+    }
+
+    for tag in [b"head", b"name", b"cmap", b"maxp", b"hhea", b"hmtx"] {
+        if font.table(tag).is_none() {
+            report.error(format!("Missing required table '{}'", String::from_utf8_lossy(tag)));
+        }
+    }
+    let has = |tag: &[u8; 4]| font.table(tag).is_some();
+    let truetype = has(b"glyf") && has(b"loca");
+    if has(b"glyf") != has(b"loca") {
+        report.error("'glyf' and 'loca' must both be present");
+    }
+    let outlines = truetype
+        || has(b"CFF ")
+        || has(b"CFF2")
+        || (has(b"CBDT") && has(b"CBLC"))
+        || (has(b"EBDT") && has(b"EBLC"))
+        || has(b"sbix");
+    if !outlines {
+        report.error("No glyph data: expected 'glyf'/'loca', 'CFF ' or 'CFF2'");
+    }
+    if font.sfnt_version == 0x4f54_544f && !(has(b"CFF ") || has(b"CFF2")) {
+        report.error("OpenType/CFF font without a 'CFF ' or 'CFF2' table");
+    }
+
+    let head = font.table(b"head");
+    if let Some(head) = head {
+        if head.len() < 54 {
+            report.error("Table 'head' is too short");
+        } else if read_u32(head, 12) != Some(HEAD_MAGIC) {
+            report.error("Table 'head' has a bad magic number");
+        }
+    }
+    let num_glyphs = font.table(b"maxp").and_then(|maxp| read_u16(maxp, 4));
+    match num_glyphs {
+        Some(0) => report.error("Font has no glyphs (maxp.numGlyphs is 0)"),
+        None if has(b"maxp") => report.error("Table 'maxp' is too short"),
+        _ => {}
+    }
+
+    if let (Some(num_glyphs), Some(hhea), Some(hmtx)) = (num_glyphs, font.table(b"hhea"), font.table(b"hmtx")) {
+        match read_u16(hhea, 34) {
+            None => report.error("Table 'hhea' is too short"),
+            Some(0) => report.error("hhea.numberOfHMetrics is 0"),
+            Some(n) if n > num_glyphs => report.error("hhea.numberOfHMetrics exceeds the glyph count"),
+            Some(n) => {
+                let needed = n as usize * 4 + (num_glyphs - n) as usize * 2;
+                if hmtx.len() < needed {
+                    report.error(format!("Table 'hmtx' is too short ({} bytes, need {needed})", hmtx.len()));
+                }
+            }
+        }
+    }
+
+    if truetype && let (Some(num_glyphs), Some(head), Some(loca), Some(glyf)) =
+        (num_glyphs, head, font.table(b"loca"), font.table(b"glyf"))
+    {
+        check_loca(head, loca, glyf, num_glyphs as usize, report);
+    }
+
+    if whole_file
+        && let Some(rec) = font.tables.iter().find(|t| &t.tag == b"head")
+        && rec.length >= 12
+    {
+        let stored = read_u32(data, rec.offset as usize + 8).unwrap_or(0);
+        let mut copy = data.to_vec();
+        copy[rec.offset as usize + 8..rec.offset as usize + 12].fill(0);
+        let expected = 0xb1b0_afbau32.wrapping_sub(checksum(&copy));
+        if stored != expected {
+            report.warn("head.checkSumAdjustment does not match the file");
+        }
+    }
+}
+
+fn check_loca(head: &[u8], loca: &[u8], glyf: &[u8], num_glyphs: usize, report: &mut Report) {
+    let long = match read_u16(head, 50) {
+        Some(0) => false,
+        Some(1) => true,
+        _ => return report.error("head.indexToLocFormat is invalid"),
+    };
+    let entry = if long { 4 } else { 2 };
+    if loca.len() < (num_glyphs + 1) * entry {
+        return report.error(format!(
+            "Table 'loca' is too short for {num_glyphs} glyphs ({} bytes)",
+            loca.len()
+        ));
+    }
+    let offset = |i: usize| {
+        if long {
+            read_u32(loca, i * 4).unwrap_or(0) as usize
+        } else {
+            read_u16(loca, i * 2).unwrap_or(0) as usize * 2
+        }
+    };
+    let mut previous = 0;
+    for i in 0..=num_glyphs {
+        let current = offset(i);
+        if current < previous {
+            return report.error(format!("Table 'loca' is not ascending at glyph {i}"));
+        }
+        if current > glyf.len() {
+            return report.error(format!("Glyph {i} points past the end of 'glyf'"));
+        }
+        previous = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sfnt::{Table, build_sfnt};
+
+    fn open_sans() -> Vec<u8> {
+        crate::woff::decode_woff2(include_bytes!("../testdata/OpenSans-Italic.woff2")).unwrap()
+    }
+
+    fn errors(data: &[u8]) -> Vec<String> {
+        check(data).into_iter().filter(|d| d.severity == Severity::Error).map(|d| d.message).collect()
+    }
+
+    // Rebuilds the font with `edit` applied to one of its tables
+    fn edited(data: &[u8], tag: &[u8; 4], edit: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        let font = Font::parse(data).unwrap();
+        let mut tables: Vec<_> = font.tables.iter()
+            .map(|rec| Table { tag: rec.tag, data: font.table(&rec.tag).unwrap().to_vec() })
+            .collect();
+        edit(&mut tables.iter_mut().find(|t| &t.tag == tag).unwrap().data);
+        build_sfnt(font.sfnt_version, tables)
+    }
+
+    // loca entries as stored, in whichever format head declares
+    fn loca_entry(data: &[u8], i: usize) -> (usize, usize) {
+        let font = Font::parse(data).unwrap();
+        let long = read_u16(font.table(b"head").unwrap(), 50) == Some(1);
+        if long { (i * 4, 4) } else { (i * 2, 2) }
+    }
+
+    #[test]
+    fn accepts_a_sound_font() {
+        assert!(check(&open_sans()).is_empty());
+    }
+
+    #[test]
+    fn catches_documents_saved_as_fonts() {
+        let html = errors(b"\n  <!DOCTYPE html><html><body>Not found</body></html>");
+        assert_eq!(html, ["File is an HTML page, not a font"]);
+        assert_eq!(errors(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), ["File is an XML/SVG document, not a font"]);
+        assert_eq!(errors(b"{\"error\": \"rate limited\"}"), ["File is a JSON document, not a font"]);
+        assert_eq!(errors(b""), ["File is empty, not a font"]);
+    }
+
+    #[test]
+    fn catches_truncated_downloads() {
+        let data = open_sans();
+        let half = errors(&data[..data.len() / 2]);
+        assert_eq!(half.len(), 1);
+        assert!(half[0].starts_with("File is truncated: table(s) "), "{half:?}");
+        assert!(!errors(&data[..10]).is_empty());
+    }
+
+    #[test]
+    fn checksum_mismatches_are_only_warnings() {
+        let mut data = open_sans();
+        let font = Font::parse(&data).unwrap();
+        let glyf = font.tables.iter().find(|t| &t.tag == b"glyf").unwrap().offset as usize;
+        data[glyf + 8] ^= 0xff;
+        let diagnostics = check(&data);
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+        assert!(diagnostics.iter().any(|d| d.message.starts_with("Checksum mismatch in table 'glyf'")));
+    }
+
+    #[test]
+    fn catches_broken_loca() {
+        let data = open_sans();
+        let (at, width) = loca_entry(&data, 2);
+
+        let past_end = edited(&data, b"loca", |loca| loca[at..at + width].fill(0xff));
+        assert!(errors(&past_end).contains(&"Glyph 2 points past the end of 'glyf'".to_string()), "{:?}", errors(&past_end));
+
+        // .notdef has an outline, so glyph 2 starting at 0 goes backwards
+        let descending = edited(&data, b"loca", |loca| loca[at..at + width].fill(0));
+        assert!(errors(&descending).contains(&"Table 'loca' is not ascending at glyph 2".to_string()), "{:?}", errors(&descending));
+
+        let short = edited(&data, b"loca", |loca| loca.truncate(loca.len() / 2));
+        assert!(errors(&short).iter().any(|e| e.starts_with("Table 'loca' is too short")), "{:?}", errors(&short));
+    }
+
+    #[test]
+    fn catches_missing_tables() {
+        let data = open_sans();
+        let font = Font::parse(&data).unwrap();
+        let tables = font.tables.iter()
+            .filter(|rec| &rec.tag != b"cmap" && &rec.tag != b"loca")
+            .map(|rec| Table { tag: rec.tag, data: font.table(&rec.tag).unwrap().to_vec() })
+            .collect();
+        let errors = errors(&build_sfnt(font.sfnt_version, tables));
+        assert!(errors.contains(&"Missing required table 'cmap'".to_string()));
+        assert!(errors.contains(&"'glyf' and 'loca' must both be present".to_string()));
+    }
+}
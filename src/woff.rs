@@ -0,0 +1,664 @@
+// Decoding of WOFF and WOFF2 web fonts back into plain sfnt files.
+// WOFF is zlib per table; WOFF2 is one Brotli stream plus the glyf/loca and
+// hmtx transforms, which are reversed here as described in the W3C spec.
+
+use std::io::{self, Read};
+
+use flate2::read::ZlibDecoder;
+
+use crate::sfnt::{Table, build_sfnt, read_u16, read_u32};
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn truncated() -> io::Error {
+    invalid("Truncated WOFF2 data")
+}
+
+/// Far beyond any real font. The sizes a web font header claims are checked
+/// against it before anything is decompressed, and decompressed archive
+/// entries and gzipped bitmap fonts are cut off at it, so a forged file
+/// cannot exhaust memory or disk.
+pub(crate) const MAX_SFNT_SIZE: u64 = 256 << 20;
+
+fn too_large() -> io::Error {
+    invalid(format!("Web font claims more than {} MiB of tables", MAX_SFNT_SIZE >> 20))
+}
+
+/// The sfnt flavor a web font wraps: 0x00010000 for TrueType, `OTTO` for CFF.
+fn flavor(data: &[u8]) -> Option<u32> {
+    read_u32(data, 4)
+}
+
+pub fn decode_woff(data: &[u8]) -> io::Result<Vec<u8>> {
+    let flavor = flavor(data).ok_or_else(|| invalid("Truncated WOFF header"))?;
+    let num_tables = read_u16(data, 12).ok_or_else(|| invalid("Truncated WOFF header"))? as usize;
+
+    let mut tables = Vec::with_capacity(num_tables);
+    let mut total = 0u64;
+    for i in 0..num_tables {
+        let rec = 44 + i * 20;
+        let (Some(tag), Some(offset), Some(comp_length), Some(orig_length)) = (
+            data.get(rec..rec + 4),
+            read_u32(data, rec + 4),
+            read_u32(data, rec + 8),
+            read_u32(data, rec + 12),
+        ) else {
+            return Err(invalid("Truncated WOFF table directory"));
+        };
+        total += u64::from(orig_length);
+        if total > MAX_SFNT_SIZE {
+            return Err(too_large());
+        }
+        let start = offset as usize;
+        let raw = data.get(start..start + comp_length as usize)
+            .ok_or_else(|| invalid("WOFF table data out of bounds"))?;
+        let table = if comp_length < orig_length {
+            let mut out = Vec::new();
+            ZlibDecoder::new(raw).take(orig_length as u64).read_to_end(&mut out)?;
+            out
+        } else {
+            raw.to_vec()
+        };
+        if table.len() != orig_length as usize {
+            return Err(invalid("WOFF table decompressed to the wrong size"));
+        }
+        tables.push(Table { tag: [tag[0], tag[1], tag[2], tag[3]], data: table });
+    }
+    Ok(build_sfnt(flavor, tables))
+}
+
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm", b"glyf", b"loca",
+    b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern", b"LTSH", b"PCLT", b"VDMX", b"vhea",
+    b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC", b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL",
+    b"SVG ", b"sbix", b"acnt", b"avar", b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar",
+    b"gvar", b"hsty", b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let out = self.data.get(self.pos..self.pos + n).ok_or_else(truncated)?;
+        self.pos += n;
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> io::Result<i16> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn base128(&mut self) -> io::Result<u32> {
+        let mut value: u32 = 0;
+        for i in 0..5 {
+            let byte = self.u8()?;
+            if i == 0 && byte == 0x80 {
+                return Err(invalid("UIntBase128 with leading zeros"));
+            }
+            if value & 0xfe00_0000 != 0 {
+                return Err(invalid("UIntBase128 overflow"));
+            }
+            value = (value << 7) | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("UIntBase128 longer than 5 bytes"))
+    }
+
+    fn u255(&mut self) -> io::Result<u16> {
+        Ok(match self.u8()? {
+            253 => self.u16()?,
+            254 => u16::from(self.u8()?) + 253 * 2,
+            255 => u16::from(self.u8()?) + 253,
+            code => u16::from(code),
+        })
+    }
+}
+
+struct DirEntry {
+    tag: [u8; 4],
+    orig_length: u32,
+    transform_length: Option<u32>,
+}
+
+pub fn decode_woff2(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut header = Reader::new(data);
+    header.bytes(4)?;                                    // signature
+    let flavor = header.u32()?;
+    if flavor == u32::from_be_bytes(*b"ttcf") {
+        return Err(invalid("WOFF2 collections are not supported"));
+    }
+    header.u32()?;                                       // length
+    let num_tables = header.u16()? as usize;
+    header.u16()?;                                       // reserved
+    header.u32()?;                                       // totalSfntSize
+    let compressed_size = header.u32()? as usize;
+    header.bytes(24)?;                                   // versions, metadata and private blocks
+
+    let mut dir = Vec::with_capacity(num_tables);
+    for _ in 0..num_tables {
+        let flags = header.u8()?;
+        let tag = match flags & 0x3f {
+            63 => {
+                let b = header.bytes(4)?;
+                [b[0], b[1], b[2], b[3]]
+            }
+            i => *KNOWN_TAGS[i as usize],
+        };
+        let version = flags >> 6;
+        let orig_length = header.base128()?;
+        // glyf/loca use version 0 for "transformed" and 3 for "as is"; every
+        // other table is the other way round
+        let transformed = match &tag {
+            b"glyf" | b"loca" => version == 0,
+            _ => version != 0,
+        };
+        let transform_length = if transformed { Some(header.base128()?) } else { None };
+        dir.push(DirEntry { tag, orig_length, transform_length });
+    }
+
+    let compressed = header.bytes(compressed_size).map_err(|_| invalid("Truncated WOFF2 font data"))?;
+    let total: u64 = dir.iter().map(|e| u64::from(e.transform_length.unwrap_or(e.orig_length))).sum();
+    let sfnt_total: u64 = dir.iter().map(|e| u64::from(e.orig_length)).sum();
+    if total > MAX_SFNT_SIZE || sfnt_total > MAX_SFNT_SIZE {
+        return Err(too_large());
+    }
+    let mut stream = Vec::new();
+    brotli_decompressor::Decompressor::new(compressed, 4096)
+        .take(total)
+        .read_to_end(&mut stream)?;
+    if stream.len() as u64 != total {
+        return Err(invalid("WOFF2 font data decompressed to the wrong size"));
+    }
+
+    let mut raw = Vec::with_capacity(dir.len());
+    let mut pos = 0;
+    for entry in &dir {
+        let len = entry.transform_length.unwrap_or(entry.orig_length) as usize;
+        raw.push(&stream[pos..pos + len]);
+        pos += len;
+    }
+
+    let find = |tag: &[u8; 4]| dir.iter().position(|e| &e.tag == tag);
+    let mut tables = Vec::with_capacity(dir.len());
+    let mut x_mins = None;
+    if let Some(glyf) = find(b"glyf").filter(|&i| dir[i].transform_length.is_some()) {
+        let loca = find(b"loca").ok_or_else(|| invalid("Transformed glyf without loca"))?;
+        let (glyf_data, loca_data, mins) = reconstruct_glyf(raw[glyf])?;
+        if loca_data.len() != dir[loca].orig_length as usize {
+            return Err(invalid("Reconstructed loca has the wrong size"));
+        }
+        tables.push(Table { tag: *b"glyf", data: glyf_data });
+        tables.push(Table { tag: *b"loca", data: loca_data });
+        x_mins = Some(mins);
+    }
+    for (i, entry) in dir.iter().enumerate() {
+        if tables.iter().any(|t| t.tag == entry.tag) {
+            continue;
+        }
+        let data = match (&entry.tag, entry.transform_length) {
+            (b"hmtx", Some(_)) => {
+                let x_mins = x_mins.as_deref().ok_or_else(|| invalid("Transformed hmtx without glyf"))?;
+                let num_h_metrics = find(b"hhea").and_then(|h| read_u16(raw[h], 34))
+                    .ok_or_else(|| invalid("Transformed hmtx without hhea"))?;
+                reconstruct_hmtx(raw[i], num_h_metrics as usize, x_mins)?
+            }
+            (_, Some(_)) => return Err(invalid("Unsupported WOFF2 table transform")),
+            (_, None) => raw[i].to_vec(),
+        };
+        tables.push(Table { tag: entry.tag, data });
+    }
+    Ok(build_sfnt(flavor, tables))
+}
+
+/// Rebuilds glyf and loca from the transformed glyf table; also returns each
+/// glyph's xMin, which a transformed hmtx needs for its side bearings.
+fn reconstruct_glyf(data: &[u8]) -> io::Result<(Vec<u8>, Vec<u8>, Vec<i16>)> {
+    let mut header = Reader::new(data);
+    header.u16()?;                                       // reserved
+    let option_flags = header.u16()?;
+    let num_glyphs = header.u16()? as usize;
+    let index_format = header.u16()?;
+    let mut sizes = [0usize; 7];
+    for size in &mut sizes {
+        *size = header.u32()? as usize;
+    }
+    let mut n_contours = Reader::new(header.bytes(sizes[0])?);
+    let mut n_points = Reader::new(header.bytes(sizes[1])?);
+    let mut flags = Reader::new(header.bytes(sizes[2])?);
+    let mut glyphs = Reader::new(header.bytes(sizes[3])?);
+    let mut composites = Reader::new(header.bytes(sizes[4])?);
+    let mut bboxes = Reader::new(header.bytes(sizes[5])?);
+    let mut instructions = Reader::new(header.bytes(sizes[6])?);
+    let overlap = if option_flags & 1 != 0 {
+        Some(header.bytes(num_glyphs.div_ceil(8))?)
+    } else {
+        None
+    };
+
+    let bbox_bitmap = bboxes.bytes(4 * num_glyphs.div_ceil(32))?;
+    let has_bbox = |i: usize| bbox_bitmap[i / 8] & (0x80 >> (i % 8)) != 0;
+
+    let mut glyf = Vec::new();
+    let mut offsets = Vec::with_capacity(num_glyphs + 1);
+    let mut x_mins = Vec::with_capacity(num_glyphs);
+    for i in 0..num_glyphs {
+        offsets.push(glyf.len());
+        let contours = n_contours.i16()?;
+        if contours == 0 {
+            if has_bbox(i) {
+                return Err(invalid("Empty glyph with a bounding box"));
+            }
+            x_mins.push(0);
+            continue;
+        }
+
+        if contours < 0 {
+            if !has_bbox(i) {
+                return Err(invalid("Composite glyph without a bounding box"));
+            }
+            let bbox = bboxes.bytes(8)?;
+            glyf.extend_from_slice(&contours.to_be_bytes());
+            glyf.extend_from_slice(bbox);
+            x_mins.push(i16::from_be_bytes([bbox[0], bbox[1]]));
+
+            let mut have_instructions = false;
+            loop {
+                let flag = composites.u16()?;
+                let mut len = 4;                         // flags + glyphIndex
+                len += if flag & 0x0001 != 0 { 4 } else { 2 };
+                if flag & 0x0008 != 0 {
+                    len += 2;
+                } else if flag & 0x0040 != 0 {
+                    len += 4;
+                } else if flag & 0x0080 != 0 {
+                    len += 8;
+                }
+                glyf.extend_from_slice(&flag.to_be_bytes());
+                glyf.extend_from_slice(composites.bytes(len - 2)?);
+                have_instructions |= flag & 0x0100 != 0;
+                if flag & 0x0020 == 0 {
+                    break;
+                }
+            }
+            if have_instructions {
+                let len = glyphs.u255()?;
+                glyf.extend_from_slice(&len.to_be_bytes());
+                glyf.extend_from_slice(instructions.bytes(len as usize)?);
+            }
+        } else {
+            let mut end_points = Vec::with_capacity(contours as usize);
+            let mut total = 0u32;
+            for _ in 0..contours {
+                total += u32::from(n_points.u255()?);
+                end_points.push(total.checked_sub(1).ok_or_else(|| invalid("Contour without points"))?);
+            }
+            let point_flags = flags.bytes(total as usize)?;
+            let points = decode_triplets(point_flags, &mut glyphs)?;
+            let instruction_len = glyphs.u255()?;
+            let instruction_bytes = instructions.bytes(instruction_len as usize)?;
+
+            let bbox = if has_bbox(i) {
+                let b = bboxes.bytes(8)?;
+                [0, 2, 4, 6].map(|o| i16::from_be_bytes([b[o], b[o + 1]]))
+            } else {
+                let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+                let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+                let max_x = points.iter().map(|p| p.0).max().unwrap_or(0);
+                let max_y = points.iter().map(|p| p.1).max().unwrap_or(0);
+                [min_x, min_y, max_x, max_y].map(|v| v as i16)
+            };
+            x_mins.push(bbox[0]);
+
+            glyf.extend_from_slice(&contours.to_be_bytes());
+            for v in bbox {
+                glyf.extend_from_slice(&v.to_be_bytes());
+            }
+            for end in end_points {
+                let end = u16::try_from(end).map_err(|_| invalid("Too many points in glyph"))?;
+                glyf.extend_from_slice(&end.to_be_bytes());
+            }
+            glyf.extend_from_slice(&instruction_len.to_be_bytes());
+            glyf.extend_from_slice(instruction_bytes);
+            let overlaps = overlap.is_some_and(|bits| bits[i / 8] & (0x80 >> (i % 8)) != 0);
+            encode_points(&points, overlaps, &mut glyf);
+        }
+        // Long loca needs no padding, but keeping glyphs 4-byte aligned is
+        // what the reference decoder does and keeps short loca valid too
+        while glyf.len() % 4 != 0 {
+            glyf.push(0);
+        }
+    }
+    offsets.push(glyf.len());
+
+    let mut loca = Vec::with_capacity((num_glyphs + 1) * 4);
+    for offset in offsets {
+        if index_format == 0 {
+            let half = u16::try_from(offset / 2).map_err(|_| invalid("glyf too large for short loca"))?;
+            loca.extend_from_slice(&half.to_be_bytes());
+        } else {
+            loca.extend_from_slice(&(offset as u32).to_be_bytes());
+        }
+    }
+    Ok((glyf, loca, x_mins))
+}
+
+fn decode_triplets(point_flags: &[u8], glyphs: &mut Reader) -> io::Result<Vec<(i32, i32, bool)>> {
+    fn with_sign(flag: u8, value: i32) -> i32 {
+        if flag & 1 != 0 { value } else { -value }
+    }
+
+    let (mut x, mut y) = (0i32, 0i32);
+    let mut points = Vec::with_capacity(point_flags.len());
+    for &raw in point_flags {
+        let on_curve = raw >> 7 == 0;
+        let flag = raw & 0x7f;
+        let (dx, dy) = if flag < 10 {
+            let b = i32::from(glyphs.u8()?);
+            (0, with_sign(flag, (i32::from(flag & 14) << 7) + b))
+        } else if flag < 20 {
+            let b = i32::from(glyphs.u8()?);
+            (with_sign(flag, (i32::from((flag - 10) & 14) << 7) + b), 0)
+        } else if flag < 84 {
+            let b0 = i32::from(flag - 20);
+            let b1 = i32::from(glyphs.u8()?);
+            (
+                with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+                with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)),
+            )
+        } else if flag < 120 {
+            let b0 = i32::from(flag - 84);
+            let b = glyphs.bytes(2)?;
+            (
+                with_sign(flag, 1 + ((b0 / 12) << 8) + i32::from(b[0])),
+                with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + i32::from(b[1])),
+            )
+        } else if flag < 124 {
+            let b = glyphs.bytes(3)?;
+            (
+                with_sign(flag, (i32::from(b[0]) << 4) + (i32::from(b[1]) >> 4)),
+                with_sign(flag >> 1, ((i32::from(b[1]) & 0x0f) << 8) + i32::from(b[2])),
+            )
+        } else {
+            let b = glyphs.bytes(4)?;
+            (
+                with_sign(flag, (i32::from(b[0]) << 8) + i32::from(b[1])),
+                with_sign(flag >> 1, (i32::from(b[2]) << 8) + i32::from(b[3])),
+            )
+        };
+        x += dx;
+        y += dy;
+        points.push((x, y, on_curve));
+    }
+    Ok(points)
+}
+
+// Writes points in the regular TrueType simple-glyph encoding: flags (with
+// runs folded into REPEAT_FLAG), then x deltas, then y deltas.
+fn encode_points(points: &[(i32, i32, bool)], overlaps: bool, out: &mut Vec<u8>) {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+    const OVERLAP_SIMPLE: u8 = 0x40;
+
+    let mut point_flags = Vec::with_capacity(points.len());
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    let (mut last_x, mut last_y) = (0, 0);
+    for (i, &(x, y, on_curve)) in points.iter().enumerate() {
+        let mut flag = if on_curve { ON_CURVE } else { 0 };
+        if i == 0 && overlaps {
+            flag |= OVERLAP_SIMPLE;
+        }
+        let (dx, dy) = (x - last_x, y - last_y);
+        (last_x, last_y) = (x, y);
+
+        if dx == 0 {
+            flag |= X_SAME_OR_POSITIVE;
+        } else if dx.abs() <= 255 {
+            flag |= X_SHORT | if dx > 0 { X_SAME_OR_POSITIVE } else { 0 };
+            xs.push(dx.unsigned_abs() as u8);
+        } else {
+            xs.extend_from_slice(&(dx as i16).to_be_bytes());
+        }
+        if dy == 0 {
+            flag |= Y_SAME_OR_POSITIVE;
+        } else if dy.abs() <= 255 {
+            flag |= Y_SHORT | if dy > 0 { Y_SAME_OR_POSITIVE } else { 0 };
+            ys.push(dy.unsigned_abs() as u8);
+        } else {
+            ys.extend_from_slice(&(dy as i16).to_be_bytes());
+        }
+        point_flags.push(flag);
+    }
+
+    let mut i = 0;
+    while i < point_flags.len() {
+        let flag = point_flags[i];
+        let mut run = 0;
+        while run < 255 && i + run + 1 < point_flags.len() && point_flags[i + run + 1] == flag {
+            run += 1;
+        }
+        if run > 0 {
+            out.push(flag | REPEAT);
+            out.push(run as u8);
+        } else {
+            out.push(flag);
+        }
+        i += run + 1;
+    }
+    out.extend_from_slice(&xs);
+    out.extend_from_slice(&ys);
+}
+
+fn reconstruct_hmtx(data: &[u8], num_h_metrics: usize, x_mins: &[i16]) -> io::Result<Vec<u8>> {
+    let num_glyphs = x_mins.len();
+    if num_h_metrics == 0 || num_h_metrics > num_glyphs {
+        return Err(invalid("Invalid numberOfHMetrics for transformed hmtx"));
+    }
+    let mut r = Reader::new(data);
+    let flags = r.u8()?;
+    let mut advances = Vec::with_capacity(num_h_metrics);
+    for _ in 0..num_h_metrics {
+        advances.push(r.u16()?);
+    }
+    let mut lsbs = Vec::with_capacity(num_glyphs);
+    for (i, &x_min) in x_mins.iter().enumerate() {
+        // Bit 0 drops the proportional bearings, bit 1 the monospaced ones;
+        // either way they equal the glyph's xMin
+        let derived = if i < num_h_metrics { flags & 1 != 0 } else { flags & 2 != 0 };
+        lsbs.push(if derived { x_min } else { r.i16()? });
+    }
+
+    let mut out = Vec::with_capacity(num_h_metrics * 4 + (num_glyphs - num_h_metrics) * 2);
+    for (i, lsb) in lsbs.iter().enumerate() {
+        if i < num_h_metrics {
+            out.extend_from_slice(&advances[i].to_be_bytes());
+        }
+        out.extend_from_slice(&lsb.to_be_bytes());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sfnt::Font;
+
+    // Open Sans Italic as shipped with rustdoc: transformed glyf, loca and hmtx
+    const OPEN_SANS: &[u8] = include_bytes!("../testdata/OpenSans-Italic.woff2");
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        read_u16(data, offset).unwrap()
+    }
+
+    fn i16_at(data: &[u8], offset: usize) -> i16 {
+        u16_at(data, offset) as i16
+    }
+
+    // Every point of a simple glyph, read back from the regular encoding
+    fn simple_points(glyph: &[u8]) -> Vec<(i32, i32)> {
+        let contours = i16_at(glyph, 0) as usize;
+        let count = u16_at(glyph, 10 + 2 * (contours - 1)) as usize + 1;
+        let mut pos = 10 + 2 * contours;
+        pos += 2 + u16_at(glyph, pos) as usize;
+        let mut flags = Vec::with_capacity(count);
+        while flags.len() < count {
+            let flag = glyph[pos];
+            pos += 1;
+            flags.push(flag);
+            if flag & 0x08 != 0 {
+                flags.extend(std::iter::repeat_n(flag, glyph[pos] as usize));
+                pos += 1;
+            }
+        }
+        let mut coordinate = |flag: u8, short: u8, same: u8| -> i32 {
+            if flag & short != 0 {
+                pos += 1;
+                let value = i32::from(glyph[pos - 1]);
+                if flag & same != 0 { value } else { -value }
+            } else if flag & same != 0 {
+                0
+            } else {
+                pos += 2;
+                i32::from(i16_at(glyph, pos - 2))
+            }
+        };
+        let mut x = 0;
+        let xs: Vec<i32> = flags.iter().map(|&f| { x += coordinate(f, 0x02, 0x10); x }).collect();
+        let mut y = 0;
+        let ys: Vec<i32> = flags.iter().map(|&f| { y += coordinate(f, 0x04, 0x20); y }).collect();
+        xs.into_iter().zip(ys).collect()
+    }
+
+    // The outline of glyph `i`, with composites resolved; None for components
+    // placed other than by a plain offset
+    fn outline(glyf: &[u8], offsets: &[usize], i: usize) -> Option<Vec<(i32, i32)>> {
+        let glyph = &glyf[offsets[i]..offsets[i + 1]];
+        if glyph.is_empty() {
+            return Some(Vec::new());
+        }
+        if i16_at(glyph, 0) > 0 {
+            return Some(simple_points(glyph));
+        }
+        let mut points = Vec::new();
+        let mut pos = 10;
+        loop {
+            let flags = u16_at(glyph, pos);
+            if flags & 0x0002 == 0 || flags & 0x00c8 != 0 {
+                return None;
+            }
+            let component = u16_at(glyph, pos + 2) as usize;
+            let (dx, dy) = if flags & 0x0001 != 0 {
+                (i32::from(i16_at(glyph, pos + 4)), i32::from(i16_at(glyph, pos + 6)))
+            } else {
+                (i32::from(glyph[pos + 4] as i8), i32::from(glyph[pos + 5] as i8))
+            };
+            pos += if flags & 0x0001 != 0 { 8 } else { 6 };
+            points.extend(outline(glyf, offsets, component)?.into_iter().map(|(x, y)| (x + dx, y + dy)));
+            if flags & 0x0020 == 0 {
+                return Some(points);
+            }
+        }
+    }
+
+    #[test]
+    fn reconstructs_glyf_loca_and_hmtx() {
+        let sfnt = decode_woff2(OPEN_SANS).unwrap();
+        let font = Font::parse(&sfnt).unwrap();
+        assert_eq!(font.names().postscript.as_deref(), Some("OpenSans-Italic"));
+
+        let head = font.table(b"head").unwrap();
+        let num_glyphs = u16_at(font.table(b"maxp").unwrap(), 4) as usize;
+        let num_h_metrics = u16_at(font.table(b"hhea").unwrap(), 34) as usize;
+        let glyf = font.table(b"glyf").unwrap();
+        let loca = font.table(b"loca").unwrap();
+        let offsets: Vec<usize> = (0..=num_glyphs)
+            .map(|i| match i16_at(head, 50) {
+                0 => u16_at(loca, i * 2) as usize * 2,
+                _ => read_u32(loca, i * 4).unwrap() as usize,
+            })
+            .collect();
+        assert!(offsets.is_sorted());
+        assert_eq!(offsets[num_glyphs], glyf.len());
+
+        let hmtx = font.table(b"hmtx").unwrap();
+        assert_eq!(hmtx.len(), num_h_metrics * 4 + (num_glyphs - num_h_metrics) * 2);
+
+        // Composites keep the bounding box of the original font, so their
+        // resolved outlines check the reconstructed simple glyphs too
+        let mut composites = 0;
+        for i in 0..num_glyphs {
+            let glyph = &glyf[offsets[i]..offsets[i + 1]];
+            if glyph.is_empty() {
+                continue;
+            }
+            let bbox = [2, 4, 6, 8].map(|o| i32::from(i16_at(glyph, o)));
+            let lsb = if i < num_h_metrics {
+                i16_at(hmtx, i * 4 + 2)
+            } else {
+                i16_at(hmtx, num_h_metrics * 4 + (i - num_h_metrics) * 2)
+            };
+            assert_eq!(i32::from(lsb), bbox[0], "side bearing of glyph {i}");
+            let Some(points) = outline(glyf, &offsets, i) else { continue };
+            let extent = [
+                points.iter().map(|p| p.0).min().unwrap(),
+                points.iter().map(|p| p.1).min().unwrap(),
+                points.iter().map(|p| p.0).max().unwrap(),
+                points.iter().map(|p| p.1).max().unwrap(),
+            ];
+            assert_eq!(extent, bbox, "outline of glyph {i}");
+            composites += usize::from(i16_at(glyph, 0) < 0);
+        }
+        assert!(composites > 100, "only {composites} composite glyphs checked");
+    }
+
+    #[test]
+    fn refuses_oversized_headers() {
+        // 100 tables of 4 GiB each, and eight bytes of "compressed" data
+        let mut data = b"wOF2\0\x01\0\0\0\0\0\0\0\x64\0\0\xff\xff\xff\xff\0\0\0\x08".to_vec();
+        data.extend_from_slice(&[0; 24]);
+        for _ in 0..100 {
+            data.extend_from_slice(&[0x00, 0x8f, 0xff, 0xff, 0xff, 0x7f]);
+        }
+        data.extend_from_slice(&[0; 8]);
+        let e = decode_woff2(&data).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("MiB"), "{e}");
+
+        let mut data = b"wOFF\0\x01\0\0\0\0\0\0\0\x01".to_vec();
+        data.resize(44, 0);
+        data.extend_from_slice(b"cmap\0\0\0\x40\0\0\0\x04\xff\xff\xff\xf0\0\0\0\0");
+        data.extend_from_slice(&[0x78, 0x9c, 0x03, 0x00]);
+        let e = decode_woff(&data).unwrap_err();
+        assert!(e.to_string().contains("MiB"), "{e}");
+    }
+}